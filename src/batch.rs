@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::time::Instant;
+use serde::{Deserialize, Serialize};
+use crate::datatype::{Problem, SolutionSet, Type};
+use crate::prioritization::{get_solution_from_solution_set_by_priorities, Priority};
+use crate::search::{SearchLimits, SearchOutcome};
+use crate::unifier::Unifier;
+
+/// How every problem of a batch is solved.
+#[derive(Clone, Debug, Default)]
+pub struct BatchOptions {
+    pub limits: SearchLimits,
+    /// The priorities the solutions are filtered by, in order.
+    pub priorities: Vec<Priority>,
+}
+
+/// A problem of a batch, read from a single line of JSON such as
+/// `{"id": "example_1", "signature": {"u32": "star"}, "problem": [...]}`. The signature holds the
+/// types of the constants, and optionally of the metavariables. See docs/json.md for the JSON of
+/// the types and the problem.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct BatchProblem {
+    /// An arbitrary value that is copied to the result, to tell the results apart.
+    #[serde(default)]
+    pub id: serde_json::Value,
+    #[serde(default)]
+    pub signature: HashMap<String, Type>,
+    pub problem: Problem,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchStatus {
+    /// The whole search tree was explored and solutions remain after filtering.
+    Solved,
+    /// The whole search tree was explored, and no solutions were found or none remain after
+    /// filtering.
+    Unsolvable,
+    /// The limits cut off part of the search tree, so there may be more solutions than the ones
+    /// found.
+    Incomplete,
+    /// The line could not be read as a problem, or the problem is ill-typed.
+    Error,
+}
+
+/// The result of a problem of a batch, written as a single line of JSON.
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct BatchResult {
+    pub id: serde_json::Value,
+    /// The line number of the problem in the input, starting at 1.
+    pub line: usize,
+    pub status: BatchStatus,
+    /// The solutions, after filtering by the priorities.
+    pub solutions: SolutionSet,
+    /// The number of nodes of the search tree that were explored.
+    pub explored: usize,
+    /// The time spent on the problem in milliseconds, including parsing and type checking.
+    pub time_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Solve the problems of the input, one per line, and write one result per line to the output.
+/// Empty lines are skipped. Returns the number of lines with an error.
+pub fn solve_batch(input: impl BufRead, mut output: impl Write, options: &BatchOptions) -> std::io::Result<usize> {
+    let mut errors = 0;
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result = solve_line(&line, index + 1, options);
+        if result.status == BatchStatus::Error {
+            errors += 1;
+        }
+
+        let json = serde_json::to_string(&result).map_err(std::io::Error::other)?;
+        writeln!(output, "{}", json)?;
+    }
+
+    Ok(errors)
+}
+
+/// Solve the problem on a single line of the input.
+pub fn solve_line(line: &str, line_number: usize, options: &BatchOptions) -> BatchResult {
+    let start = Instant::now();
+
+    let mut result = BatchResult {
+        id: serde_json::Value::Null,
+        line: line_number,
+        status: BatchStatus::Error,
+        solutions: SolutionSet(vec![]),
+        explored: 0,
+        time_ms: 0.0,
+        error: None,
+    };
+
+    match serde_json::from_str::<BatchProblem>(line) {
+        Ok(problem) => {
+            result.id = problem.id.clone();
+            solve_problem(problem, options, &mut result);
+        }
+        Err(error) => result.error = Some(error.to_string()),
+    }
+
+    result.time_ms = start.elapsed().as_secs_f64() * 1000.0;
+    result
+}
+
+fn solve_problem(problem: BatchProblem, options: &BatchOptions, result: &mut BatchResult) {
+    let unifier = Unifier::new(problem.signature).with_limits(options.limits.clone());
+
+    let mut search = match unifier.search(problem.problem) {
+        Ok(search) => search,
+        Err(error) => {
+            result.error = Some(error.to_string());
+            return;
+        }
+    };
+    let solutions = SolutionSet(search.by_ref().map(|solution| solution.minimize(&unifier.name_map)).collect());
+    let solutions = get_solution_from_solution_set_by_priorities(solutions, &options.priorities);
+
+    // The status describes the solutions in the result, which are the filtered ones.
+    result.status = match search.outcome() {
+        SearchOutcome::Incomplete => BatchStatus::Incomplete,
+        SearchOutcome::Complete if solutions.0.is_empty() => BatchStatus::Unsolvable,
+        SearchOutcome::Complete => BatchStatus::Solved,
+    };
+    result.explored = search.explored();
+    result.solutions = solutions;
+}
+
+#[cfg(feature = "batch")]
+#[test]
+fn test_solve_batch() {
+    // Arrange
+    let signature = r#""signature": {"u32": "star", "bool": "star", "option": {"arrow": ["star", "star"]}}"#;
+    let left = r#"{"app": [{"meta": "I"}, {"var": "u32"}]}"#;
+    let right = r#"{"app": [{"var": "option"}, {"var": "u32"}]}"#;
+    let wrong = r#"{"app": [{"var": "option"}, {"var": "bool"}]}"#;
+
+    let input = [
+        format!(r#"{{"id": 1, {}, "problem": [{{"left": {}, "right": {}}}]}}"#, signature, left, right),
+        String::new(),
+        format!(r#"{{"id": "two", {}, "problem": [{{"left": {}, "right": {}}}, {{"left": {}, "right": {}}}]}}"#, signature, left, right, left, wrong),
+        "not json".to_string(),
+    ].join("\n");
+
+    let mut output = vec![];
+
+    // Act
+    let errors = solve_batch(input.as_bytes(), &mut output, &BatchOptions::default()).unwrap();
+
+    // Assert
+    let results = String::from_utf8(output).unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+
+    assert_eq!(errors, 1);
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0]["id"], 1);
+    assert_eq!(results[0]["status"], "solved");
+    assert_eq!(results[0]["solutions"].as_array().unwrap().len(), 2);
+    assert_eq!(results[1]["id"], "two");
+    assert_eq!(results[1]["line"], 3);
+    assert_eq!(results[1]["status"], "unsolvable");
+    assert_eq!(results[2]["status"], "error");
+    assert!(results[2]["error"].is_string());
+}
+
+#[cfg(feature = "batch")]
+#[test]
+fn test_solve_line_with_options() {
+    // Arrange
+    let signature = r#""signature": {"u32": "star", "option": {"arrow": ["star", "star"]}}"#;
+    let diverging = r#"[{"left": {"app": [{"meta": "I"}, {"app": [{"var": "option"}, {"var": "u32"}]}]}, "right": {"app": [{"var": "option"}, {"app": [{"meta": "I"}, {"var": "u32"}]}]}}]"#;
+    let line = format!(r#"{{{}, "problem": {}}}"#, signature, diverging);
+
+    let options = BatchOptions {
+        limits: SearchLimits { max_depth: Some(3), ..SearchLimits::default() },
+        priorities: vec![crate::prioritization::generality],
+    };
+
+    // Act
+    let actual = solve_line(&line, 1, &options);
+
+    // Assert
+    assert_eq!(actual.status, BatchStatus::Incomplete);
+    assert_eq!(actual.error, None);
+    assert!(actual.explored > 0);
+}
@@ -0,0 +1,316 @@
+use std::io::Read;
+use std::process::ExitCode;
+use crate::repl::Repl;
+use higher_order_unification::{get_solution_from_solution_set_by_priorities, parse_input, parse_problem_file, Context, ParseError, PrintMode, print_mode_for, Priority, priority_by_name, Problem, ProblemSpec, SearchLimits, SearchOutcome, set_print_mode, SolutionSet, Stream, Trace, TypeError, Unifier, PRIORITIES};
+
+mod repl;
+
+const USAGE: &str = "\
+Usage: hou [OPTIONS] [PROBLEM]
+       hou --interactive [OPTIONS]
+       hou --batch [OPTIONS]
+
+Solve a higher-order unification problem with Huet's pre-unification procedure. The problem is
+preceded by the declarations of its constants, and optionally of its metavariables, as in
+`const u32 : *; const option : * -> *; I u32 =? option u32`. It is read from stdin if neither
+PROBLEM nor --file is given, or if PROBLEM is `-`.
+
+Options:
+  -i, --interactive        Start a REPL, where the problem is built and solved step by step
+  -b, --batch              Solve the problems of a JSON lines file or stdin, one per line, and
+                           print one JSON result per line. See docs/json.md for the JSON format
+  -f, --file PATH          Read the problem from a file. A problem file with named problems
+                           solves each of them and checks their expected solutions
+  -p, --priorities LIST    Filter the solutions by the comma-separated priorities, in order,
+                           or by all of them with `all`
+  -d, --depth N            Stop the search at N imitations and projections
+  -n, --nodes N            Stop the search after exploring N nodes
+      --format FORMAT      Print the solutions as a `set` (default) or one per line with `lines`
+      --color WHEN         Colour the output: `auto` (default), `always` or `never`
+      --trace FORMAT       Print the search tree instead of the solutions, as `text`, as
+                           Graphviz with `dot` or as `json`. `json` needs the `batch` feature
+  -h, --help               Print this help";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Format {
+    Set,
+    Lines,
+}
+
+/// How the search tree is printed.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TraceFormat {
+    Text,
+    Dot,
+    Json,
+}
+
+#[derive(Debug)]
+struct Options {
+    problem: Option<String>,
+    file: Option<String>,
+    priorities: Vec<Priority>,
+    limits: SearchLimits,
+    format: Format,
+    color: PrintMode,
+    trace: Option<TraceFormat>,
+    interactive: bool,
+    batch: bool,
+}
+
+/// The input, either a single problem or the named problems of a problem file.
+enum Input {
+    Single(Context, Problem),
+    File(Vec<ProblemSpec>),
+}
+
+fn main() -> ExitCode {
+    let options = match parse_arguments(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(message) => {
+            eprintln!("error: {}\n\n{}", message, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    set_print_mode(options.color);
+
+    if options.interactive {
+        Repl::new(options.limits.clone()).run(std::io::stdin().lock());
+        return ExitCode::SUCCESS;
+    }
+
+    #[cfg(feature = "batch")]
+    if options.batch {
+        return batch(&options);
+    }
+
+    let source = match read_source(&options) {
+        Ok(source) => source,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let input = match parse_source(&source) {
+        Ok(input) => input,
+        Err(error) => {
+            eprintln!("{}", error);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match input {
+        Input::Single(context, problem) => match solve(&options, &Unifier::new(context.typing_context), problem) {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(error) => {
+                eprintln!("error: {}", error.print_in(print_mode_for(Stream::Stderr)));
+                ExitCode::FAILURE
+            }
+        },
+        Input::File(specs) => {
+            let mut failed = 0;
+
+            for spec in specs {
+                println!("problem {}: {}", spec.name, spec.problem);
+
+                match solve(&options, &spec.unifier(), spec.problem.clone()) {
+                    Ok(solutions) if spec.is_expected(&solutions) => {}
+                    Ok(_) => {
+                        println!("problem {} does not have the expected solutions", spec.name);
+                        failed += 1;
+                    }
+                    Err(error) => {
+                        println!("error: {}", error);
+                        failed += 1;
+                    }
+                }
+            }
+
+            if failed == 0 { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+        }
+    }
+}
+
+/// Parse the arguments, returning `None` if the help was requested.
+fn parse_arguments(mut arguments: impl Iterator<Item = String>) -> Result<Option<Options>, String> {
+    let mut options = Options {
+        problem: None,
+        file: None,
+        priorities: vec![],
+        limits: SearchLimits::default(),
+        format: Format::Set,
+        color: PrintMode::Auto,
+        trace: None,
+        interactive: false,
+        batch: false,
+    };
+
+    while let Some(argument) = arguments.next() {
+        let mut value = |name: &str| arguments.next().ok_or(format!("{} expects a value", name));
+
+        match argument.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-i" | "--interactive" => options.interactive = true,
+            "-b" | "--batch" if cfg!(feature = "batch") => options.batch = true,
+            "-b" | "--batch" => return Err("hou was built without the `batch` feature".to_string()),
+            "-f" | "--file" => options.file = Some(value(&argument)?),
+            "-p" | "--priorities" => options.priorities = parse_priorities(&value(&argument)?)?,
+            "-d" | "--depth" => options.limits.max_depth = Some(parse_number(&argument, &value(&argument)?)?),
+            "-n" | "--nodes" => options.limits.max_nodes = Some(parse_number(&argument, &value(&argument)?)?),
+            "--format" => options.format = match value(&argument)?.as_str() {
+                "set" => Format::Set,
+                "lines" => Format::Lines,
+                other => return Err(format!("unknown format `{}`", other)),
+            },
+            "--color" => options.color = match value(&argument)?.as_str() {
+                "auto" => PrintMode::Auto,
+                "always" => PrintMode::Ansi,
+                "never" => PrintMode::Plain,
+                other => return Err(format!("unknown colour mode `{}`", other)),
+            },
+            "--trace" => options.trace = Some(parse_trace_format(&value(&argument)?)?),
+            _ if argument.starts_with('-') && argument != "-" => return Err(format!("unknown option `{}`", argument)),
+            _ if options.problem.is_some() => return Err(format!("unexpected argument `{}`", argument)),
+            _ => options.problem = Some(argument),
+        }
+    }
+
+    if options.problem.is_some() && options.file.is_some() {
+        return Err("give either a problem or a file, not both".to_string());
+    }
+
+    if options.interactive && (options.problem.is_some() || options.file.is_some()) {
+        return Err("the REPL does not take a problem or a file".to_string());
+    }
+
+    if options.batch && (options.interactive || options.problem.is_some()) {
+        return Err("the batch mode only takes a file".to_string());
+    }
+
+    Ok(Some(options))
+}
+
+fn parse_priorities(list: &str) -> Result<Vec<Priority>, String> {
+    if list == "all" {
+        return Ok(PRIORITIES.iter().map(|(_, priority)| *priority).collect());
+    }
+
+    list.split(',')
+        .map(|name| priority_by_name(name.trim()).ok_or(format!("unknown priority `{}`", name.trim())))
+        .collect()
+}
+
+fn parse_trace_format(format: &str) -> Result<TraceFormat, String> {
+    match format {
+        "text" => Ok(TraceFormat::Text),
+        "dot" => Ok(TraceFormat::Dot),
+        "json" if cfg!(feature = "batch") => Ok(TraceFormat::Json),
+        "json" => Err("the JSON trace needs the `batch` feature".to_string()),
+        other => Err(format!("unknown trace format `{}`", other)),
+    }
+}
+
+fn render_trace(trace: &Trace, format: TraceFormat) -> String {
+    match format {
+        TraceFormat::Text => trace.to_string(),
+        TraceFormat::Dot => trace.to_dot(),
+        #[cfg(feature = "batch")]
+        TraceFormat::Json => format!("{}\n", serde_json::to_string_pretty(trace).expect("a trace can always be serialized")),
+        #[cfg(not(feature = "batch"))]
+        TraceFormat::Json => unreachable!("the JSON trace is rejected without the `batch` feature"),
+    }
+}
+
+fn parse_number(option: &str, value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| format!("{} expects a number, not `{}`", option, value))
+}
+
+fn read_source(options: &Options) -> Result<String, String> {
+    match (&options.problem, &options.file) {
+        (Some(problem), _) if problem != "-" => Ok(problem.clone()),
+        (_, Some(file)) => std::fs::read_to_string(file).map_err(|error| format!("could not read {}: {}", file, error)),
+        _ => {
+            let mut source = String::new();
+            std::io::stdin().read_to_string(&mut source).map_err(|error| format!("could not read stdin: {}", error))?;
+            Ok(source)
+        }
+    }
+}
+
+/// Parse the source as a single problem or as a problem file. If it is neither, the error that
+/// got furthest into the source is the most relevant one.
+fn parse_source(source: &str) -> Result<Input, ParseError> {
+    let single = match parse_input(source) {
+        Ok((context, problem)) => return Ok(Input::Single(context, problem)),
+        Err(error) => error,
+    };
+
+    match parse_problem_file(source) {
+        Ok(specs) => Ok(Input::File(specs)),
+        Err(error) if (error.start.line, error.start.column) > (single.start.line, single.start.column) => Err(error),
+        Err(_) => Err(single),
+    }
+}
+
+/// Solve the problems of the file or stdin as a batch.
+#[cfg(feature = "batch")]
+fn batch(options: &Options) -> ExitCode {
+    let batch_options = higher_order_unification::BatchOptions {
+        limits: options.limits.clone(),
+        priorities: options.priorities.clone(),
+    };
+
+    let result = match &options.file {
+        Some(file) => std::fs::File::open(file)
+            .and_then(|file| higher_order_unification::solve_batch(std::io::BufReader::new(file), std::io::stdout().lock(), &batch_options)),
+        None => higher_order_unification::solve_batch(std::io::stdin().lock(), std::io::stdout().lock(), &batch_options),
+    };
+
+    match result {
+        Ok(0) => ExitCode::SUCCESS,
+        Ok(_) => ExitCode::FAILURE,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Check the problem, run the search with the inferred types of the metavariables and print the
+/// filtered solutions, or why there are none, or the search tree if a trace was requested.
+/// Returns all the solutions, before filtering.
+fn solve(options: &Options, unifier: &Unifier, problem: Problem) -> Result<SolutionSet, TypeError> {
+    let unifier = unifier.clone().with_limits(options.limits.clone());
+
+    if let Some(format) = options.trace {
+        let (solutions, _, trace) = unifier.solve_traced(problem)?;
+        print!("{}", render_trace(&trace, format));
+        return Ok(solutions);
+    }
+
+    let (solutions, explanation) = unifier.solve_explained(problem)?;
+    let filtered = get_solution_from_solution_set_by_priorities(solutions.clone(), &options.priorities);
+
+    match options.format {
+        Format::Set => print!("{}", filtered),
+        Format::Lines => {
+            for solution in &filtered.0 {
+                println!("{}", solution);
+            }
+        }
+    }
+
+    if solutions.0.is_empty() {
+        eprint!("{}", explanation.print_in(print_mode_for(Stream::Stderr)));
+    } else if explanation.outcome == SearchOutcome::Incomplete {
+        eprintln!("warning: the search was cut off by the limits, so there may be more solutions");
+    }
+
+    Ok(solutions)
+}
@@ -0,0 +1,204 @@
+use std::io::{BufRead, Write};
+use higher_order_unification::{get_solution_from_solution_set_by_priorities, parse_declaration, parse_problem, Constraint, Declaration, Explanation, Problem, SearchLimits, SearchOutcome, SolutionSet, Trace, Unifier};
+use crate::{parse_number, parse_priorities, parse_trace_format, render_trace, TraceFormat};
+
+const HELP: &str = "\
+Commands:
+  const NAME : TYPE      Declare a constant
+  meta NAME : TYPE       Declare the type of a metavariable
+  add CONSTRAINTS        Add constraints, separated by ∧. `add` may be left out
+  remove N               Remove the constraint numbered N
+  clear                  Remove all the constraints
+  show                   Show the declarations and the constraints
+  solve                  Solve the constraints and list the solutions
+  solutions              List the solutions of the last run
+  filter LIST            Filter the solutions of the last run by the comma-separated priorities,
+                         or by all of them with `all`
+  why                    Explain why the branches of the last run failed
+  tree [FORMAT]          Show the search tree of the last run, as `text` (default), as
+                         Graphviz with `dot` or as `json`
+  depth N|off            Limit the depth of the search
+  nodes N|off            Limit the number of explored nodes
+  help                   Show this help
+  quit                   Leave the REPL";
+
+/// The result of the last `solve`.
+struct Run {
+    solutions: SolutionSet,
+    trace: Trace,
+    explanation: Explanation,
+}
+
+/// An interactive session, where the problem is built up one constraint at a time.
+pub struct Repl {
+    unifier: Unifier,
+    declarations: Vec<Declaration>,
+    constraints: Vec<Constraint>,
+    last: Option<Run>,
+}
+
+impl Repl {
+    pub fn new(limits: SearchLimits) -> Repl {
+        Repl {
+            unifier: Unifier::default().with_limits(limits),
+            declarations: vec![],
+            constraints: vec![],
+            last: None,
+        }
+    }
+
+    /// Read and execute commands until the input ends or the session is left.
+    pub fn run(&mut self, input: impl BufRead) {
+        println!("Type `help` for the list of commands.");
+        prompt();
+
+        for line in input.lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(error) => {
+                    eprintln!("error: could not read the input: {}", error);
+                    return;
+                }
+            };
+
+            match self.execute(line.trim()) {
+                Ok(true) => {}
+                Ok(false) => return,
+                Err(message) => eprintln!("error: {}", message),
+            }
+
+            prompt();
+        }
+
+        println!();
+    }
+
+    /// Execute a single command, returning whether the session continues.
+    fn execute(&mut self, line: &str) -> Result<bool, String> {
+        let (command, argument) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let argument = argument.trim();
+
+        match command {
+            "" => {}
+            "help" => println!("{}", HELP),
+            "quit" | "exit" => return Ok(false),
+            "const" | "meta" => self.declare(line)?,
+            "add" => self.add(argument)?,
+            "remove" => {
+                let number = parse_number("remove", argument)?;
+                if number == 0 || number > self.constraints.len() {
+                    return Err(format!("there is no constraint {}", number));
+                }
+                self.constraints.remove(number - 1);
+                self.show_constraints();
+            }
+            "clear" => self.constraints.clear(),
+            "show" => {
+                for declaration in &self.declarations {
+                    match declaration {
+                        Declaration::Const(name, typ) => println!("const {} : {}", name, typ),
+                        Declaration::Meta(name, typ) => println!("meta {} : {}", name, typ),
+                    }
+                }
+                self.show_constraints();
+            }
+            "solve" => self.solve()?,
+            "solutions" => self.show_solutions(&self.last()?.solutions),
+            "filter" => {
+                let priorities = parse_priorities(argument)?;
+                let filtered = get_solution_from_solution_set_by_priorities(self.last()?.solutions.clone(), &priorities);
+                self.show_solutions(&filtered);
+            }
+            "why" => print!("{}", self.last()?.explanation),
+            "tree" => {
+                let format = if argument.is_empty() { TraceFormat::Text } else { parse_trace_format(argument)? };
+                print!("{}", render_trace(&self.last()?.trace, format));
+            }
+            "depth" => self.unifier.limits.max_depth = parse_limit("depth", argument)?,
+            "nodes" => self.unifier.limits.max_nodes = parse_limit("nodes", argument)?,
+            _ if line.contains("=?") => self.add(line)?,
+            _ => return Err(format!("unknown command `{}`, type `help` for the list of commands", command)),
+        }
+
+        Ok(true)
+    }
+
+    fn declare(&mut self, line: &str) -> Result<(), String> {
+        let source = if line.ends_with(';') { line.to_string() } else { format!("{};", line) };
+        let declaration = parse_declaration(&source).map_err(|error| error.to_string())?;
+
+        let (name, typ) = match &declaration {
+            Declaration::Const(name, typ) | Declaration::Meta(name, typ) => (name, typ),
+        };
+        self.unifier.declare(name, typ.clone());
+
+        self.declarations.retain(|existing| match existing {
+            Declaration::Const(existing, _) | Declaration::Meta(existing, _) => existing != name,
+        });
+        self.declarations.push(declaration);
+        Ok(())
+    }
+
+    fn add(&mut self, source: &str) -> Result<(), String> {
+        let problem = parse_problem(source).map_err(|error| error.to_string())?;
+        self.constraints.extend(problem.0);
+        self.show_constraints();
+        Ok(())
+    }
+
+    fn solve(&mut self) -> Result<(), String> {
+        let problem = Problem(self.constraints.clone());
+
+        let mut search = self.unifier.search(problem).map_err(|error| error.to_string())?.with_trace();
+        let solutions = search.by_ref().map(|solution| solution.minimize(&self.unifier.name_map)).collect();
+
+        let run = Run {
+            solutions: SolutionSet(solutions),
+            trace: search.trace().cloned().unwrap_or_default(),
+            explanation: search.explanation(),
+        };
+
+        self.show_solutions(&run.solutions);
+        println!("Explored {} nodes.", search.explored());
+        if run.solutions.0.is_empty() {
+            print!("{}", run.explanation);
+        } else if run.explanation.outcome == SearchOutcome::Incomplete {
+            println!("The search was cut off by the limits, so there may be more solutions.");
+        }
+
+        self.last = Some(run);
+        Ok(())
+    }
+
+    fn last(&self) -> Result<&Run, String> {
+        self.last.as_ref().ok_or("nothing has been solved yet, use `solve` first".to_string())
+    }
+
+    fn show_constraints(&self) {
+        for (index, constraint) in self.constraints.iter().enumerate() {
+            println!("{}: {}", index + 1, constraint);
+        }
+    }
+
+    fn show_solutions(&self, solutions: &SolutionSet) {
+        if solutions.0.is_empty() {
+            println!("No solutions.");
+        }
+
+        for (index, solution) in solutions.0.iter().enumerate() {
+            println!("{}: {}", index + 1, solution);
+        }
+    }
+}
+
+fn prompt() {
+    print!("hou> ");
+    let _ = std::io::stdout().flush();
+}
+
+fn parse_limit(option: &str, value: &str) -> Result<Option<usize>, String> {
+    match value {
+        "off" => Ok(None),
+        _ => parse_number(option, value).map(Some),
+    }
+}
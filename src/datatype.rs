@@ -1,5 +1,5 @@
 use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt::{Debug};
 use std::rc::Rc;
 use std::str::FromStr;
@@ -7,12 +7,14 @@ use std::string::ToString;
 use std::sync::atomic::{AtomicU32, Ordering};
 use crate::datatype::Term::{Abs, App, Var};
 use crate::datatype::Type::Star;
-use crate::substs::{beta_reduce, term_substitution};
+use crate::substs::{beta_reduce, meta_substitution};
 use crate::util::amount_of_swaps_to_sort;
 
-const PLACEHOLDER: &'static str = "placeholder";
+const PLACEHOLDER: &str = "placeholder";
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Term {
     Meta(String),
     Var(String),
@@ -21,6 +23,8 @@ pub enum Term {
 }
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Type {
     Star,
     Arrow(Box<Type>, Box<Type>)
@@ -34,27 +38,78 @@ pub struct Context {
     pub name_map: HashMap<String, Vec<String>>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+/// Two constraints are equal if their sides are, regardless of their provenance.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Constraint {
     pub left: Term,
-    pub right: Term
+    pub right: Term,
+    /// The constraints of the original problem this constraint was derived from.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Provenance::is_empty"))]
+    pub provenance: Provenance,
 }
 
-#[derive(Clone, PartialEq, Debug)]
+/// Two substitutions are equal if they substitute the same term for the same name, regardless
+/// of their provenance.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Substitution {
     pub name: String,
-    pub with: Term
+    pub with: Term,
+    /// The constraints of the original problem that forced this substitution.
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Provenance::is_empty"))]
+    pub provenance: Provenance,
 }
 
+/// The indices of constraints of the original problem of a search. The search gives each
+/// constraint of the problem its own index, which the constraints it is decomposed into inherit.
+/// A substitution is forced by the constraint it solves, and applying it to another constraint
+/// adds its provenance to that constraint.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Provenance(pub BTreeSet<usize>);
+
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Problem(pub Vec<Constraint>);
 
+/// The substitutions of a solution, and the flex-flex constraints that remain unsolved. As in
+/// Huet's pre-unification, a problem of only flex-flex constraints is always solvable, so the
+/// search stops there and returns them.
 #[derive(Clone, PartialEq, Debug)]
-pub struct Solution(pub Vec<Substitution>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Solution(pub Vec<Substitution>, pub Vec<Constraint>);
 
 #[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SolutionSet(pub Vec<Solution>);
 
+/// A declaration of the signature of a problem: the type of a constant, as in
+/// `const option : * -> *;`, or of a metavariable, as in `meta P : * -> * -> *;`.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Declaration {
+    Const(String, Type),
+    Meta(String, Type),
+}
+
+/// The reason a branch of the search failed.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Clash {
+    /// The metavariable occurs in a rigid position of the other side of the constraint, so no
+    /// substitution can make the two sides equal.
+    Occurs(String, Constraint),
+    /// The two sides of the rigid-rigid constraint have different heads, the first for the left
+    /// side and the second for the right side.
+    Heads(Term, Term, Constraint),
+    /// The other side of the pattern constraint depends on a bound variable that the
+    /// metavariable is not applied to.
+    Scope(String, String, Constraint),
+}
+
 
 impl Term {
     pub fn is_rigid(&self) -> bool {
@@ -94,9 +149,13 @@ impl Term {
         }
     }
 
+    /// Put the term under the abstractors of `bindings`, as returned by `split`. The abstractors
+    /// are meant to bind the free variables of the term, so this does not avoid capture.
     pub fn combine(&self, bindings: Term) -> Term {
-        let substs = Substitution { name: PLACEHOLDER.to_string(), with: self.clone() };
-        term_substitution(bindings, substs)
+        match bindings {
+            Term::Abs(s, typ, inner) => Term::Abs(s, typ, Box::new(self.combine(*inner))),
+            _ => self.clone()
+        }
     }
 
     pub fn get_name(&self) -> String {
@@ -194,7 +253,7 @@ impl Term {
             }
 
             App(callee, call_arg) => {
-                &callee.number_of_params(bounded.clone()) + &call_arg.number_of_params(bounded)
+                callee.number_of_params(bounded.clone()) + call_arg.number_of_params(bounded)
             }
             Term::Meta(..) => 0
         }
@@ -226,60 +285,217 @@ impl Term {
             Term::Meta(..) => Vec::new()
         }
     }
+
+    /// The variables that occur in the term without being bound by an abstraction in it.
+    pub fn free_vars(&self) -> HashSet<String> {
+        match self {
+            Var(s) => HashSet::from([s.clone()]),
+            Abs(s, _, inner) => {
+                let mut free = inner.free_vars();
+                free.remove(s);
+                free
+            }
+            App(callee, call_arg) => &callee.free_vars() | &call_arg.free_vars(),
+            Term::Meta(_) => HashSet::new()
+        }
+    }
+
+    /// Whether the metavariable occurs in the term.
+    pub fn contains_meta(&self, name: &str) -> bool {
+        match self {
+            Term::Meta(s) => s == name,
+            Var(_) => false,
+            Abs(_, _, inner) => inner.contains_meta(name),
+            App(callee, call_arg) => callee.contains_meta(name) || call_arg.contains_meta(name),
+        }
+    }
+
+    /// The number of nodes in the syntax tree of the term.
+    pub fn size(&self) -> usize {
+        match self {
+            Term::Meta(_) | Var(_) => 1,
+            Abs(_, _, inner) => 1 + inner.size(),
+            App(callee, call_arg) => 1 + callee.size() + call_arg.size()
+        }
+    }
+}
+
+impl Type {
+    /// The argument types of a function type, such that `* -> * -> *` has two arguments.
+    pub fn arguments(&self) -> Vec<Type> {
+        let mut current = self;
+        let mut arguments = vec![];
+        while let Type::Arrow(argument, result) = current {
+            arguments.push(*argument.clone());
+            current = result;
+        }
+        arguments
+    }
+
+    /// The type after applying `count` arguments.
+    pub fn result(&self, count: usize) -> Type {
+        let mut current = self;
+        for _ in 0..count {
+            match current {
+                Type::Arrow(_, result) => current = result,
+                Type::Star => break
+            }
+        }
+        current.clone()
+    }
+
+    /// The number of arguments to apply to get the `target` type, if possible.
+    pub fn arguments_to(&self, target: &Type) -> Option<usize> {
+        (0..=self.arguments().len()).find(|count| &self.result(*count) == target)
+    }
+}
+
+impl Clash {
+    /// The constraint that can not be solved.
+    pub fn constraint(&self) -> &Constraint {
+        match self {
+            Clash::Occurs(_, constraint) | Clash::Heads(_, _, constraint) | Clash::Scope(_, _, constraint) => constraint,
+        }
+    }
+}
+
+impl PartialEq for Constraint {
+    fn eq(&self, other: &Constraint) -> bool {
+        self.left == other.left && self.right == other.right
+    }
+}
+
+impl PartialEq for Substitution {
+    fn eq(&self, other: &Substitution) -> bool {
+        self.name == other.name && self.with == other.with
+    }
+}
+
+impl Provenance {
+    /// The provenance of the constraint with the given index in the original problem.
+    pub fn of(index: usize) -> Provenance {
+        Provenance(BTreeSet::from([index]))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn extend(&mut self, other: &Provenance) {
+        self.0.extend(other.0.iter().copied());
+    }
+}
+
+impl Substitution {
+    /// A substitution without provenance.
+    pub fn new(name: impl Into<String>, with: Term) -> Substitution {
+        Substitution { name: name.into(), with, provenance: Provenance::default() }
+    }
 }
 
 impl Constraint {
+    /// A constraint without provenance.
+    pub fn new(left: Term, right: Term) -> Constraint {
+        Constraint { left, right, provenance: Provenance::default() }
+    }
+
     pub fn is_rigid_rigid(&self) -> bool {
         let (_, l_head, _) = self.left.split();
         let (_, r_head, _) = self.right.split();
 
         l_head.is_rigid() && r_head.is_rigid()
     }
+
+    pub fn is_flex_flex(&self) -> bool {
+        let (_, l_head, _) = self.left.split();
+        let (_, r_head, _) = self.right.split();
+
+        !l_head.is_rigid() && !r_head.is_rigid()
+    }
 }
 
+static COUNTER: AtomicU32 = AtomicU32::new(0);
+
 pub fn generate_fresh_var() -> String {
-    static COUNTER: AtomicU32 = AtomicU32::new(0);
     format!("{:?}", COUNTER.fetch_add(1, Ordering::Relaxed))
 }
 
+/// Make sure the numeric name is never generated as a fresh variable, because it is already
+/// written in the input.
+pub(crate) fn reserve_fresh_var(name: &str) {
+    if let Ok(number) = u32::from_str(name) {
+        COUNTER.fetch_max(number.saturating_add(1), Ordering::Relaxed);
+    }
+}
+
+/// The next fresh variable. Numeric names below it were generated or written before this point.
+pub(crate) fn fresh_var_mark() -> u32 {
+    COUNTER.load(Ordering::Relaxed)
+}
+
 impl Solution {
-    pub fn minimize(self, name_map: &HashMap<String, Vec<String>>) -> Solution {
+    /// Compose the substitutions for the fresh metavariables into the ones for the other
+    /// metavariables, and drop them. The search does this before it yields a solution.
+    pub(crate) fn compose(self, is_fresh: impl Fn(&str) -> bool) -> Solution {
         let mut originals =
             self.0.iter()
-                .filter(|substitution| u32::from_str(&substitution.name).is_err())
+                .filter(|substitution| !is_fresh(&substitution.name))
                 .cloned()
                 .collect::<Vec<_>>();
 
         for sub in self.0 {
             for original in &mut originals {
-                original.with = term_substitution(original.with.clone(), sub.clone())
+                if original.with.contains_meta(&sub.name) {
+                    original.provenance.extend(&sub.provenance);
+                }
+                original.with = meta_substitution(original.with.clone(), sub.clone())
             }
         }
 
-        for original in &mut originals {
-            if let Some(list) = name_map.get(&original.name) {
-                let mut builder = original.with.clone();
-                for element in list {
-                    builder = beta_reduce(App(Box::new(builder), Box::new(Var(element.to_string()))))
-                }
+        Solution(originals, self.1)
+    }
 
-                for element in list.iter().rev() {
-                    builder = Abs(element.to_string(), Star, Box::new(builder))
-                }
+    /// Compose the substitutions and apply the name map.
+    pub fn minimize(self, name_map: &HashMap<String, Vec<String>>) -> Solution {
+        self.compose(|_| false).name_map(name_map)
+    }
 
-                original.with = builder;
-            }
-        }
+    /// Whether the solutions substitute alpha-equivalent terms for the same metavariables, in
+    /// any order, and have alpha-equivalent residuals.
+    pub fn alpha_equivalent(&self, other: &Solution) -> bool {
+        self.equivalent(other, Term::alpha_equivalent)
+    }
+
+    /// Whether the solutions are equivalent up to alpha- and eta-conversion.
+    pub fn eta_equivalent(&self, other: &Solution) -> bool {
+        self.equivalent(other, Term::eta_equivalent)
+    }
+
+    fn equivalent(&self, other: &Solution, terms: fn(&Term, &Term) -> bool) -> bool {
+        let substitutions = self.0.len() == other.0.len() && self.0.iter().all(|substitution| {
+            other.0.iter().any(|o| o.name == substitution.name && terms(&o.with, &substitution.with))
+        });
+
+        let residuals = self.1.len() == other.1.len() && self.1.iter().all(|constraint| {
+            other.1.iter().any(|o| terms(&o.left, &constraint.left) && terms(&o.right, &constraint.right))
+        });
+
+        substitutions && residuals
+    }
 
-        Solution(originals)
+    /// For every substitution of the minimized solution, the constraints of the problem that
+    /// forced it. The problem is the one the solution was searched for.
+    pub fn sources<'a>(&self, problem: &'a Problem) -> Vec<(&Substitution, Vec<&'a Constraint>)> {
+        self.0.iter()
+            .map(|substitution| {
+                let constraints = substitution.provenance.0.iter().filter_map(|index| problem.0.get(*index)).collect();
+                (substitution, constraints)
+            })
+            .collect()
     }
 
     pub fn name_map(self, name_map: &HashMap<String, Vec<String>>) -> Solution {
-        let mut originals =
-            self.0.iter()
-                .filter(|substitution| u32::from_str(&substitution.name).is_err())
-                .cloned()
-                .collect::<Vec<_>>();
+        let mut originals = self.0;
 
         for original in &mut originals {
             if let Some(list) = name_map.get(&original.name) {
@@ -296,7 +512,7 @@ impl Solution {
             }
         }
 
-        Solution(originals)
+        Solution(originals, self.1)
     }
 
     pub fn number_of_constants(&self) -> usize {
@@ -335,13 +551,44 @@ impl Substitution {
     }
 }
 
+impl SolutionSet {
+    /// Remove the solutions that are alpha-equivalent to an earlier one.
+    pub fn dedup(self) -> SolutionSet {
+        self.dedup_by(Solution::alpha_equivalent)
+    }
+
+    /// Remove the solutions that are equivalent to an earlier one up to alpha- and
+    /// eta-conversion.
+    pub fn dedup_modulo_eta(self) -> SolutionSet {
+        self.dedup_by(Solution::eta_equivalent)
+    }
+
+    fn dedup_by(self, equivalent: fn(&Solution, &Solution) -> bool) -> SolutionSet {
+        let mut distinct: Vec<Solution> = vec![];
+        for solution in self.0 {
+            if !distinct.iter().any(|existing| equivalent(existing, &solution)) {
+                distinct.push(solution);
+            }
+        }
+        SolutionSet(distinct)
+    }
+}
+
 impl Context {
+    /// The minimized solutions found so far, in the order they were found, without the ones
+    /// that are alpha-equivalent to an earlier one.
     pub fn minimal_solutions(&self) -> SolutionSet {
-        SolutionSet(self.solutions.borrow().iter().cloned().map(|solution| solution.minimize(&self.name_map)).collect())
+        self.found_solutions(&self.name_map).dedup()
     }
 
     pub fn minimal_solutions_without_name_map(&self) -> SolutionSet {
-        SolutionSet(self.solutions.borrow().iter().cloned().map(|solution| solution.minimize(&HashMap::new())).collect())
+        self.found_solutions(&HashMap::new()).dedup()
+    }
+
+    /// The minimized solutions found so far, in the order they were found, including the
+    /// duplicates.
+    pub fn found_solutions(&self, name_map: &HashMap<String, Vec<String>>) -> SolutionSet {
+        SolutionSet(self.solutions.borrow().iter().cloned().map(|solution| solution.minimize(name_map)).collect())
     }
 }
 
@@ -375,3 +622,66 @@ fn test_split() {
     // Assert
     assert_eq!(actual, expected);
 }
+
+#[test]
+fn test_dedup_solutions() {
+    // Arrange
+    let solution = |substitutions: &[(&str, &str)]| Solution(
+        substitutions.iter()
+            .map(|(name, term)| Substitution::new(*name, crate::parse::parse_term(term).unwrap()))
+            .collect(),
+        vec![]
+    );
+
+    let solutions = SolutionSet(vec![
+        solution(&[("I", "λx:*. option x"), ("J", "u32")]),
+        solution(&[("J", "u32"), ("I", "λy:*. option y")]),
+        solution(&[("I", "option"), ("J", "u32")]),
+        solution(&[("I", "λx:*. x"), ("J", "u32")]),
+    ]);
+
+    // Act
+    let alpha = solutions.clone().dedup();
+    let eta = solutions.dedup_modulo_eta();
+
+    // Assert
+    assert_eq!(alpha.0.len(), 3);
+    assert_eq!(eta.0.len(), 2);
+    assert_eq!(eta.0[1], solution(&[("I", "λx:*. x"), ("J", "u32")]));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serialize_to_documented_json() {
+    // Arrange
+    let constraint = crate::parse::parse_constraint("λx:* -> *. F x =? λx:* -> *. x u32").unwrap();
+
+    // Act
+    let actual = serde_json::to_value(&constraint).unwrap();
+
+    // Assert
+    let expected = serde_json::json!({
+        "left": {"abs": ["x", {"arrow": ["star", "star"]}, {"app": [{"meta": "F"}, {"var": "x"}]}]},
+        "right": {"abs": ["x", {"arrow": ["star", "star"]}, {"app": [{"var": "x"}, {"var": "u32"}]}]}
+    });
+    assert_eq!(actual, expected);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde_round_trip() {
+    // Arrange
+    let problem = crate::parse::parse_problem("I (L u32) =? option (option u32) ∧ λy:*. P y =? λy:*. result y y").unwrap();
+    let solutions = SolutionSet(vec![
+        Solution(vec![Substitution::new("I", crate::parse::parse_term("λx:*. option x").unwrap())], vec![]),
+        Solution(vec![], problem.0.clone()),
+    ]);
+
+    // Act
+    let actual_problem: Problem = serde_json::from_str(&serde_json::to_string(&problem).unwrap()).unwrap();
+    let actual_solutions: SolutionSet = serde_json::from_str(&serde_json::to_string(&solutions).unwrap()).unwrap();
+
+    // Assert
+    assert_eq!(actual_problem, problem);
+    assert_eq!(actual_solutions, solutions);
+}
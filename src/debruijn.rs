@@ -0,0 +1,206 @@
+use crate::datatype::{generate_fresh_var, Term, Type};
+
+/// A term in the locally nameless representation: bound variables are de Bruijn indices, counting
+/// the abstractions between the variable and its binder, while free variables and metavariables
+/// keep their names. Alpha-equivalent terms have the same representation.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DeBruijn {
+    Meta(String),
+    Free(String),
+    Bound(usize),
+    Abs(Type, Box<DeBruijn>),
+    App(Box<DeBruijn>, Box<DeBruijn>)
+}
+
+impl Term {
+    pub fn to_de_bruijn(&self) -> DeBruijn {
+        self.to_de_bruijn_under(&mut vec![])
+    }
+
+    fn to_de_bruijn_under(&self, binders: &mut Vec<String>) -> DeBruijn {
+        match self {
+            Term::Meta(s) => DeBruijn::Meta(s.clone()),
+            Term::Var(s) => match binders.iter().rev().position(|binder| binder == s) {
+                Some(index) => DeBruijn::Bound(index),
+                None => DeBruijn::Free(s.clone()),
+            },
+            Term::Abs(s, typ, inner) => {
+                binders.push(s.clone());
+                let inner = inner.to_de_bruijn_under(binders);
+                binders.pop();
+                DeBruijn::Abs(typ.clone(), Box::new(inner))
+            }
+            Term::App(t1, t2) => DeBruijn::App(
+                Box::new(t1.to_de_bruijn_under(binders)),
+                Box::new(t2.to_de_bruijn_under(binders))
+            )
+        }
+    }
+
+    /// Whether the terms are equal up to the names of the bound variables.
+    pub fn alpha_equivalent(&self, other: &Term) -> bool {
+        self.to_de_bruijn() == other.to_de_bruijn()
+    }
+
+    /// Whether the terms are equal up to the names of the bound variables and eta-conversion,
+    /// such that `λx:*. f x` is equivalent to `f`.
+    pub fn eta_equivalent(&self, other: &Term) -> bool {
+        self.to_de_bruijn().eta_reduce() == other.to_de_bruijn().eta_reduce()
+    }
+}
+
+impl DeBruijn {
+    /// Convert back to the named representation, naming the binders with fresh variables.
+    pub fn to_named(&self) -> Term {
+        self.to_named_under(&mut vec![])
+    }
+
+    fn to_named_under(&self, binders: &mut Vec<String>) -> Term {
+        match self {
+            DeBruijn::Meta(s) => Term::Meta(s.clone()),
+            DeBruijn::Free(s) => Term::Var(s.clone()),
+            DeBruijn::Bound(index) => match binders.iter().rev().nth(*index) {
+                Some(name) => Term::Var(name.clone()),
+                None => panic!("The index {} is not bound", index),
+            },
+            DeBruijn::Abs(typ, inner) => {
+                binders.push(generate_fresh_var());
+                let inner = inner.to_named_under(binders);
+                let name = binders.pop().unwrap();
+                Term::Abs(name, typ.clone(), Box::new(inner))
+            }
+            DeBruijn::App(t1, t2) => Term::App(
+                Box::new(t1.to_named_under(binders)),
+                Box::new(t2.to_named_under(binders))
+            )
+        }
+    }
+
+    /// Add `amount` to the indices that are at least `cutoff`, that is the ones that are not
+    /// bound inside the term.
+    pub fn shift(&self, amount: isize, cutoff: usize) -> DeBruijn {
+        match self {
+            DeBruijn::Bound(index) if *index >= cutoff => DeBruijn::Bound((*index as isize + amount) as usize),
+            DeBruijn::Abs(typ, inner) => DeBruijn::Abs(typ.clone(), Box::new(inner.shift(amount, cutoff + 1))),
+            DeBruijn::App(t1, t2) => DeBruijn::App(
+                Box::new(t1.shift(amount, cutoff)),
+                Box::new(t2.shift(amount, cutoff))
+            ),
+            _ => self.clone()
+        }
+    }
+
+    /// Substitute `with` for the index `index`.
+    pub fn substitute(&self, index: usize, with: &DeBruijn) -> DeBruijn {
+        match self {
+            DeBruijn::Bound(i) if *i == index => with.clone(),
+            DeBruijn::Abs(typ, inner) => DeBruijn::Abs(typ.clone(), Box::new(inner.substitute(index + 1, &with.shift(1, 0)))),
+            DeBruijn::App(t1, t2) => DeBruijn::App(
+                Box::new(t1.substitute(index, with)),
+                Box::new(t2.substitute(index, with))
+            ),
+            _ => self.clone()
+        }
+    }
+
+    /// Substitute `with` for the metavariable `name`.
+    pub fn substitute_meta(&self, name: &str, with: &DeBruijn) -> DeBruijn {
+        match self {
+            DeBruijn::Meta(s) if s == name => with.clone(),
+            DeBruijn::Abs(typ, inner) => DeBruijn::Abs(typ.clone(), Box::new(inner.substitute_meta(name, &with.shift(1, 0)))),
+            DeBruijn::App(t1, t2) => DeBruijn::App(
+                Box::new(t1.substitute_meta(name, with)),
+                Box::new(t2.substitute_meta(name, with))
+            ),
+            _ => self.clone()
+        }
+    }
+
+    /// Reduce every `λ. t 0` where `0` does not occur in `t` to `t`, from the inside out.
+    pub fn eta_reduce(&self) -> DeBruijn {
+        match self {
+            DeBruijn::Abs(typ, inner) => match inner.eta_reduce() {
+                DeBruijn::App(t1, t2) if *t2 == DeBruijn::Bound(0) && !t1.occurs(0) => t1.shift(-1, 0),
+                inner => DeBruijn::Abs(typ.clone(), Box::new(inner)),
+            },
+            DeBruijn::App(t1, t2) => DeBruijn::App(Box::new(t1.eta_reduce()), Box::new(t2.eta_reduce())),
+            _ => self.clone()
+        }
+    }
+
+    /// Whether the index occurs in the term.
+    fn occurs(&self, index: usize) -> bool {
+        match self {
+            DeBruijn::Bound(i) => *i == index,
+            DeBruijn::Abs(_, inner) => inner.occurs(index + 1),
+            DeBruijn::App(t1, t2) => t1.occurs(index) || t2.occurs(index),
+            _ => false
+        }
+    }
+
+    /// Instantiate the body of an abstraction with the argument, as in a beta reduction.
+    pub fn instantiate(&self, argument: &DeBruijn) -> DeBruijn {
+        self.substitute(0, &argument.shift(1, 0)).shift(-1, 0)
+    }
+}
+
+
+#[test]
+fn test_alpha_equivalent() {
+    // Arrange
+    let left = crate::parse::parse_term("λx:*. λy:*. result x y").unwrap();
+    let right = crate::parse::parse_term("λa:*. λb:*. result a b").unwrap();
+    let swapped = crate::parse::parse_term("λa:*. λb:*. result b a").unwrap();
+
+    // Act
+    let equivalent = left.alpha_equivalent(&right);
+    let not_equivalent = left.alpha_equivalent(&swapped);
+
+    // Assert
+    assert!(equivalent);
+    assert!(!not_equivalent);
+}
+
+#[test]
+fn test_de_bruijn_round_trip() {
+    // Arrange
+    let input = crate::parse::parse_term("λx:*. λy:*. F (λz:*. x z) y u32").unwrap();
+
+    // Act
+    let actual = input.to_de_bruijn().to_named();
+
+    // Assert
+    assert!(actual.alpha_equivalent(&input));
+}
+
+#[test]
+fn test_instantiate_does_not_capture() {
+    // Arrange
+    // The body of λx:*. λy:*. x
+    let body = DeBruijn::Abs(Type::Star, Box::new(DeBruijn::Bound(1)));
+    let argument = DeBruijn::Free("y".to_string());
+
+    // Act
+    let actual = body.instantiate(&argument);
+
+    // Assert
+    assert_eq!(actual, DeBruijn::Abs(Type::Star, Box::new(DeBruijn::Free("y".to_string()))));
+}
+
+#[test]
+fn test_eta_equivalent() {
+    // Arrange
+    let expanded = crate::parse::parse_term("λx:*. λy:*. result x y").unwrap();
+    let reduced = crate::parse::parse_term("result").unwrap();
+    let partially = crate::parse::parse_term("λa:*. result a").unwrap();
+    let dependent = crate::parse::parse_term("λx:*. F x x").unwrap();
+
+    // Act
+    let equivalent = expanded.eta_equivalent(&reduced) && partially.eta_equivalent(&reduced);
+    let not_equivalent = dependent.eta_equivalent(&crate::parse::parse_term("F").unwrap());
+
+    // Assert
+    assert!(equivalent);
+    assert!(!not_equivalent);
+    assert!(!expanded.alpha_equivalent(&reduced));
+}
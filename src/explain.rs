@@ -0,0 +1,78 @@
+use crate::datatype::{Clash, Constraint};
+use crate::search::SearchOutcome;
+
+/// Why a branch of the search failed.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Failure {
+    pub clash: Clash,
+    /// The constraints of the original problem that the clashing constraint was derived from.
+    pub origins: Vec<Constraint>,
+}
+
+/// Distinct clashes, with the number of branches that failed with each of them.
+pub type ClashCounts<'a> = Vec<(&'a Clash, usize)>;
+
+/// The failed branches of a search, to explain why it found no solutions.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Explanation {
+    /// The failures, in the order they were found.
+    pub failures: Vec<Failure>,
+    /// Whether the limits cut off part of the search, in which case the unexplored branches
+    /// might have had solutions.
+    pub outcome: SearchOutcome,
+}
+
+impl Explanation {
+    /// The distinct clashes, grouped by the constraints of the original problem they come from,
+    /// together with the number of branches that failed with each of them. The groups and the
+    /// clashes are in the order they were first found.
+    pub fn by_origin(&self) -> Vec<(&[Constraint], ClashCounts<'_>)> {
+        let mut groups: Vec<(&[Constraint], ClashCounts)> = vec![];
+
+        for failure in &self.failures {
+            let index = match groups.iter().position(|(origins, _)| *origins == failure.origins) {
+                Some(index) => index,
+                None => {
+                    groups.push((&failure.origins, vec![]));
+                    groups.len() - 1
+                }
+            };
+
+            let clashes = &mut groups[index].1;
+            match clashes.iter_mut().find(|(clash, _)| **clash == failure.clash) {
+                Some((_, count)) => *count += 1,
+                None => clashes.push((&failure.clash, 1)),
+            }
+        }
+
+        groups
+    }
+}
+
+
+#[test]
+fn test_by_origin() {
+    // Arrange
+    let first = crate::parse::parse_constraint("I u32 =? option u32").unwrap();
+    let second = crate::parse::parse_constraint("I string =? option bool").unwrap();
+    let heads = crate::parse::parse_constraint("string =? bool").unwrap();
+    let clash = Clash::Heads(crate::Term::Var("string".to_string()), crate::Term::Var("bool".to_string()), heads);
+
+    let explanation = Explanation {
+        failures: vec![
+            Failure { clash: clash.clone(), origins: vec![first.clone(), second.clone()] },
+            Failure { clash: Clash::Occurs("I".to_string(), first.clone()), origins: vec![first.clone()] },
+            Failure { clash: clash.clone(), origins: vec![first.clone(), second.clone()] },
+        ],
+        outcome: SearchOutcome::Complete,
+    };
+
+    // Act
+    let actual = explanation.by_origin();
+
+    // Assert
+    assert_eq!(actual.len(), 2);
+    assert_eq!(actual[0], (&[first.clone(), second][..], vec![(&clash, 2)]));
+    assert_eq!(actual[1].0, &[first][..]);
+}
@@ -1,10 +1,19 @@
 extern crate core;
 
 
-use crate::datatype::{Context, Problem, Solution};
-use crate::r#match::match_;
-use crate::simpl::simpl;
-use crate::substs::problem_substitution;
+#[cfg(feature = "batch")]
+pub use crate::batch::{BatchOptions, BatchProblem, BatchResult, BatchStatus, solve_batch, solve_line};
+pub use crate::datatype::{Clash, Constraint, Context, Declaration, Problem, Provenance, Solution, SolutionSet, Substitution, Term, Type};
+pub use crate::debruijn::DeBruijn;
+pub use crate::explain::{ClashCounts, Explanation, Failure};
+pub use crate::parse::{Location, parse_constraint, parse_declaration, parse_input, parse_problem, parse_problem_file, parse_term, parse_type, ParseError};
+pub use crate::print::{print_mode, print_mode_for, PrintMode, set_print_mode, Stream};
+pub use crate::prioritization::{exhaustiveness, existence, generality, get_solution_from_solution_set, get_solution_from_solution_set_by_priorities, most_general, ordering, Priority, priority_by_name, PRIORITIES, simplicity};
+pub use crate::typing::TypeError;
+pub use crate::search::{Search, SearchLimits, SearchOutcome};
+pub use crate::spec::{load_problems, LoadError, ProblemSpec};
+pub use crate::trace::{NodeOutcome, Step, Trace, TraceNode};
+pub use crate::unifier::Unifier;
 
 mod datatype;
 mod substs;
@@ -14,140 +23,110 @@ mod parse;
 mod print;
 mod util;
 mod prioritization;
-
-fn main_huet(context: &mut Context, problem: Problem) {
-    let p_simpl = simpl(context.clone(), problem);
-
-    if p_simpl.is_none() {
-        return
-    }
-
-    let p_simpl = p_simpl.unwrap();
-
-    if p_simpl.0.is_empty() {
-        context.solutions.borrow_mut().push(Solution(context.substitutions.clone()));
-        return;
-    }
-
-    let constraint = p_simpl.0[0].clone();
-
-    let substitution_set = match_(context.clone(), constraint);
-
-    for substitution in substitution_set {
-        let new_problem = problem_substitution(p_simpl.clone(), substitution.clone());
-        let mut substs_for_context = context.substitutions.clone();
-        substs_for_context.push(substitution.clone());
-
-        let mut new_context = Context {
-            typing_context: context.typing_context.clone(),
-            substitutions: substs_for_context,
-            solutions: context.solutions.clone(),
-            name_map: context.name_map.clone(),
-        };
-
-        main_huet(&mut new_context, new_problem);
-    }
-}
-
-
+mod unifier;
+mod search;
+mod pattern;
+mod occurs;
+mod debruijn;
+mod typing;
+mod spec;
+mod trace;
+mod explain;
+mod subsumption;
+#[cfg(feature = "batch")]
+mod batch;
+
+#[cfg(test)]
 mod tests {
-    use std::cell::RefCell;
     use std::collections::HashMap;
-    use std::rc::Rc;
-    use crate::datatype::{Context, SolutionSet};
-    use crate::main_huet;
+    use crate::{Clash, Constraint, get_solution_from_solution_set_by_priorities, load_problems, Term, NodeOutcome, PrintMode, Step, Priority, TypeError, SearchLimits, SearchOutcome, Solution, SolutionSet, Substitution, Unifier};
     use crate::util;
-    use crate::parse::{parse_constraint, parse_problem, parse_term, parse_type};
-    use crate::prioritization::{exhaustiveness, existence, generality, get_solution_from_solution_set, get_solution_from_solution_set_by_priorities, ordering, simplicity};
+    use crate::parse::{parse_constraint, parse_input, parse_problem, parse_term, parse_type};
+    use crate::prioritization::{exhaustiveness, existence, generality, most_general, ordering, simplicity};
 
-    const WITHOUT_SIMPLICITY: &[fn(SolutionSet) -> SolutionSet] = &[existence, generality, exhaustiveness, ordering];
-    const REVERSE_ORDER: &[fn(SolutionSet) -> SolutionSet] = &[simplicity, ordering, exhaustiveness, generality, existence];
-    const NORMAL_ORDER: &[fn(SolutionSet) -> SolutionSet] = &[existence, generality, exhaustiveness, ordering, simplicity];
-    const MIXED_ORDER: &[fn(SolutionSet) -> SolutionSet] = &[existence, ordering, exhaustiveness, simplicity, generality];
+    const WITHOUT_SIMPLICITY: &[Priority] = &[existence, generality, exhaustiveness, ordering];
+    const REVERSE_ORDER: &[Priority] = &[simplicity, ordering, exhaustiveness, generality, existence];
+    const NORMAL_ORDER: &[Priority] = &[existence, generality, exhaustiveness, ordering, simplicity];
+    const MIXED_ORDER: &[Priority] = &[existence, ordering, exhaustiveness, simplicity, generality];
 
     fn run(input: &str) -> SolutionSet {
         // Arrange
-        let problem = parse_problem(input);
+        let problem = parse_problem(input).unwrap();
 
-        let mut context = generate_context();
+        let unifier = generate_unifier();
 
         // Act
-        main_huet(&mut context, problem.clone());
+        let minimal = unifier.solve(problem.clone()).unwrap().0;
 
         // Assert
         println!("Problem: {}", problem);
         println!();
-        println!("Number of solutions: {:#?}", context.solutions.borrow().len());
-        let minimal = context.minimal_solutions();
+        println!("Number of solutions: {:#?}", minimal.0.len());
         println!("Context: {}", minimal);
         minimal
     }
 
-    fn run_with_all_priorities(input: &str) {
+    fn run_with_all_priorities(input: &str) -> Option<Solution> {
         // Arrange
-        let problem = parse_problem(input);
+        let problem = parse_problem(input).unwrap();
 
-        let mut context = generate_context();
+        let unifier = generate_unifier();
 
         // Act
-        main_huet(&mut context, problem.clone());
+        let filtered = unifier.solve_best(problem.clone()).unwrap();
 
         // Assert
         println!("Problem: {}", problem);
         println!();
-        println!("Number of solutions: {:#?}", context.solutions.borrow().len());
-        let minimal = context.minimal_solutions_without_name_map();
-        let minimal_with_names = context.minimal_solutions();
-        println!("Non-filtered solutions: {}", minimal_with_names);
-        let filtered = get_solution_from_solution_set(minimal.clone());
-        match filtered.clone() {
-            Ok(solution) => println!("Filtered solutions: {}", solution.name_map(&context.name_map)),
-            Err(solutions) => println!("Filtered solutions {}", SolutionSet(solutions.0.iter().cloned().map(|s| s.name_map(&context.name_map)).collect()))
+        println!("Non-filtered solutions: {}", unifier.solve(problem).unwrap().0);
+        match &filtered {
+            Ok(solution) => println!("Filtered solutions: {}", solution),
+            Err(solutions) => println!("Filtered solutions {}", solutions)
         }
+
+        filtered.ok()
     }
 
-    fn run_with_priority(input: &str, filter: fn(SolutionSet) -> SolutionSet) -> SolutionSet {
+    fn run_with_priority(input: &str, filter: Priority) -> SolutionSet {
         run_with_priorities(input, &[filter])
     }
 
-    fn run_with_priorities(input: &str, filters: &[fn(SolutionSet) -> SolutionSet]) -> SolutionSet {
+    fn run_with_priorities(input: &str, filters: &[Priority]) -> SolutionSet {
         // Arrange
-        let problem = parse_problem(input);
+        let problem = parse_problem(input).unwrap();
 
-        let mut context = generate_context();
+        let unifier = generate_unifier();
 
         // Act
-        main_huet(&mut context, problem.clone());
+        let filtered = unifier.solve_with_priorities(problem.clone(), filters).unwrap();
 
         // Assert
         println!("Problem: {}", problem);
         println!();
-        println!("Number of solutions: {:#?}", context.solutions.borrow().len());
-        let minimal = context.minimal_solutions_without_name_map();
-        let minimal_with_names = context.minimal_solutions();
-        println!("Non-filtered solutions: {}", minimal_with_names);
-        let filtered = get_solution_from_solution_set_by_priorities(minimal.clone(), filters);
+        println!("Non-filtered solutions: {}", unifier.solve(problem).unwrap().0);
         println!("Number of filtered solutions: {:#?}", filtered.0.len());
-        println!("Filtered solutions: {}", SolutionSet(filtered.0.iter().cloned().map(|s| s.name_map(&context.name_map)).collect()));
+        println!("Filtered solutions: {}", filtered);
 
         filtered
     }
 
-    fn generate_context() -> Context {
-        Context {
+    fn solution(substitutions: &[(&str, &str)]) -> Solution {
+        Solution(substitutions.iter().map(|(name, term)| Substitution::new(*name, parse_term(term).unwrap())).collect(), vec![])
+    }
+
+    fn generate_unifier() -> Unifier {
+        Unifier {
             typing_context: HashMap::from_iter([
-                ("b".to_string(), parse_type("*")),
-                ("u32".to_string(), parse_type("*")),
-                ("bool".to_string(), parse_type("*")),
-                ("string".to_string(), parse_type("*")),
-                ("unit".to_string(), parse_type("*")),
-                ("result".to_string(), parse_type("* -> * -> *")),
-                ("option".to_string(), parse_type("* -> *")),
-                ("fn2".to_string(), parse_type("* -> * -> * -> *")),
-                ("fn3".to_string(), parse_type("* -> * -> * -> * -> *")),
+                ("b".to_string(), parse_type("*").unwrap()),
+                ("u32".to_string(), parse_type("*").unwrap()),
+                ("bool".to_string(), parse_type("*").unwrap()),
+                ("string".to_string(), parse_type("*").unwrap()),
+                ("unit".to_string(), parse_type("*").unwrap()),
+                ("result".to_string(), parse_type("* -> * -> *").unwrap()),
+                ("option".to_string(), parse_type("* -> *").unwrap()),
+                ("fn2".to_string(), parse_type("* -> * -> * -> *").unwrap()),
+                ("fn3".to_string(), parse_type("* -> * -> * -> * -> *").unwrap()),
             ]),
-            substitutions: vec![],
-            solutions: Rc::new(RefCell::new(vec![])),
             name_map: HashMap::from_iter([
                 ("F".to_string(), vec!["k".to_string()]),
                 ("I".to_string(), vec!["j".to_string()]),
@@ -156,6 +135,7 @@ mod tests {
                 ("T".to_string(), vec!["u".to_string(), "v".to_string()]),
                 ("S".to_string(), vec!["x".to_string(), "y".to_string(), "z".to_string()]),
             ]),
+            ..Unifier::default()
         }
     }
 
@@ -327,6 +307,34 @@ mod tests {
         run_with_priority("P u32 u32 =? result u32 u32", generality);
     }
 
+    #[test]
+    fn example_priority_most_general_1() {
+        let solutions = run_with_priorities("I (J u32) =? option u32", &[most_general, generality]);
+
+        assert_eq!(solutions.0.len(), 2);
+        assert!(solutions.0.iter().any(|found| found.alpha_equivalent(&solution(&[("I", "λj:*. j"), ("J", "λx:*. option x")]))));
+        assert!(solutions.0.iter().any(|found| found.alpha_equivalent(&solution(&[("I", "λj:*. option j"), ("J", "λx:*. x")]))));
+    }
+
+    #[test]
+    fn most_general_drops_the_instances() {
+        let solution = |term: &str, residuals: &str| Solution(
+            vec![Substitution::new("I", parse_term(term).unwrap())],
+            if residuals.is_empty() { vec![] } else { parse_problem(residuals).unwrap().0 }
+        );
+
+        let instance = solution("λx:*. result x x", "");
+        let general = solution("λx:*. result (?5 x) (?6 x)", "?5 u32 =? ?6 u32");
+        let unrelated = solution("λx:*. option x", "");
+        let solutions = SolutionSet(vec![instance, general.clone(), unrelated.clone()]);
+
+        let filtered = most_general(solutions.clone());
+        let tie_broken = get_solution_from_solution_set_by_priorities(solutions, &[most_general, simplicity]);
+
+        assert_eq!(filtered, SolutionSet(vec![general, unrelated.clone()]));
+        assert_eq!(tie_broken, SolutionSet(vec![unrelated]));
+    }
+
     #[test]
     fn example_priority_exhaustiveness_1() {
         run_with_priority("P u32 bool =? option (result u32 bool)", exhaustiveness);
@@ -360,7 +368,7 @@ mod tests {
 
     #[test]
     fn example_priority_ordering_4() {
-        run_with_priority("S u32 bool string =? result (fn2 u32 string) bool", ordering);
+        run_with_priority("S u32 bool string =? result (fn2 u32 string unit) bool", ordering);
     }
 
     #[test]
@@ -385,17 +393,17 @@ mod tests {
 
     #[test]
     fn example_priority_filters_1_mixed_order() {
-        run_with_priorities("S u32 bool string =? result (fn2 u32 string) bool", MIXED_ORDER);
+        run_with_priorities("S u32 bool string =? result (fn2 u32 string unit) bool", MIXED_ORDER);
     }
 
     #[test]
     fn example_priority_filters_1_reverse_order() {
-        run_with_priorities("S u32 bool string =? result (fn2 u32 string) bool", REVERSE_ORDER);
+        run_with_priorities("S u32 bool string =? result (fn2 u32 string unit) bool", REVERSE_ORDER);
     }
 
     #[test]
     fn example_priority_filters_1_normal_order() {
-        run_with_priorities("S u32 bool string =? result (fn2 u32 string) bool", NORMAL_ORDER);
+        run_with_priorities("S u32 bool string =? result (fn2 u32 string unit) bool", NORMAL_ORDER);
     }
 
     #[test]
@@ -460,17 +468,26 @@ mod tests {
 
     #[test]
     fn example_priority_10() {
-        run_with_all_priorities("P u32 u32 =? result u32 u32 ∧ T u32 u32 =? result u32 u32 ∧ P bool bool =? result bool bool ∧ T bool bool =? result bool bool");
+        let best = run_with_all_priorities("P u32 u32 =? result u32 u32 ∧ T u32 u32 =? result u32 u32 ∧ P bool bool =? result bool bool ∧ T bool bool =? result bool bool");
+
+        let expected = solution(&[("P", "λq:*. λr:*. result q r"), ("T", "λu:*. λv:*. result u v")]);
+        assert!(best.is_some_and(|best| best.alpha_equivalent(&expected)));
     }
 
     #[test]
     fn example_priority_11() {
-        run_with_all_priorities("S u32 bool string =? result (fn2 u32 string) bool");
+        let best = run_with_all_priorities("S u32 bool string =? result (fn2 u32 string unit) bool");
+
+        let expected = solution(&[("S", "λx:*. λy:*. λz:*. result (fn2 x z unit) y")]);
+        assert!(best.is_some_and(|best| best.alpha_equivalent(&expected)));
     }
 
     #[test]
     fn example_priority_12() {
-        run_with_all_priorities("P u32 u32 =? result u32 u32 ∧ P bool bool =? result bool bool");
+        let best = run_with_all_priorities("P u32 u32 =? result u32 u32 ∧ P bool bool =? result bool bool");
+
+        let expected = solution(&[("P", "λq:*. λr:*. result q r")]);
+        assert!(best.is_some_and(|best| best.alpha_equivalent(&expected)));
     }
 
     #[test]
@@ -478,27 +495,460 @@ mod tests {
         run_with_all_priorities("P (result u32 u32) u32 =? result u32 u32");
     }
 
+    #[test]
+    fn unifier_with_declared_signature() {
+        let mut unifier = Unifier::default();
+        unifier.declare("u32", parse_type("*").unwrap());
+        unifier.declare("option", parse_type("* -> *").unwrap());
+        unifier.name_binders("I", &["j"]);
+
+        let solutions = unifier.solve(parse_problem("I u32 =? option u32").unwrap()).unwrap().0;
+
+        println!("Solutions: {}", solutions);
+        assert_eq!(solutions.0.len(), 2);
+    }
+
+
+    #[test]
+    fn lazy_solutions_match_the_full_search() {
+        let unifier = generate_unifier();
+        let problem = parse_problem("P u32 u32 =? result u32 u32 ∧ T u32 u32 =? result u32 u32").unwrap();
+
+        let lazy = unifier.solutions(problem.clone()).unwrap().collect::<Vec<_>>();
+        let full = unifier.solve(problem).unwrap().0;
+
+        assert_eq!(lazy, full.0);
+    }
+
+    #[test]
+    fn lazy_solutions_can_stop_early() {
+        let unifier = generate_unifier();
+        let problem = parse_problem("I (L u32) =? option (option u32)").unwrap();
+
+        let first = unifier.solutions(problem.clone()).unwrap().next();
+        let (solutions, _) = unifier.solve(problem).unwrap();
+
+        assert_eq!(first, solutions.0.first().cloned());
+    }
+
+    #[test]
+    fn depth_limit_stops_a_diverging_search() {
+        let unifier = generate_unifier().with_limits(SearchLimits { max_depth: Some(10), ..SearchLimits::default() });
+
+        let (solutions, outcome) = unifier.solve(parse_problem("I (option u32) =? option (I u32)").unwrap()).unwrap();
+
+        assert!(!solutions.0.is_empty());
+        assert_eq!(outcome, SearchOutcome::Incomplete);
+    }
+
+    #[test]
+    fn occurs_check_fails_cyclic_problems() {
+        let unifier = generate_unifier();
+        let mut search = unifier.search(parse_problem("I u32 =? option (I u32)").unwrap()).unwrap();
+
+        assert_eq!(search.next(), None);
+        assert_eq!(search.outcome(), SearchOutcome::Complete);
+        assert!(matches!(search.clashes(), [Clash::Occurs(meta, _)] if meta == "I"));
+    }
+
+    #[test]
+    fn occurs_check_in_pattern() {
+        let unifier = generate_unifier();
+        let mut search = unifier.search(parse_problem("λx:*. I x =? λx:*. result x (I u32)").unwrap()).unwrap();
+
+        assert_eq!(search.next(), None);
+        assert!(matches!(search.clashes(), [Clash::Occurs(meta, _)] if meta == "I"));
+    }
+
+    #[test]
+    fn failures_explain_why_there_are_no_solutions() {
+        let unifier = generate_unifier();
+        let problem = parse_problem("I u32 =? option u32 ∧ I string =? option bool").unwrap();
+
+        let (solutions, explanation) = unifier.solve_explained(problem.clone()).unwrap();
+
+        println!("{}", explanation);
+        assert!(solutions.0.is_empty());
+        assert_eq!(explanation.outcome, SearchOutcome::Complete);
+        assert!(!explanation.failures.is_empty());
+        assert!(explanation.failures.iter().all(|failure| !failure.origins.is_empty()));
+        assert!(explanation.failures.iter().flat_map(|failure| &failure.origins).all(|origin| problem.0.contains(origin)));
+        assert!(explanation.failures.iter().any(|failure| matches!(
+            &failure.clash,
+            Clash::Heads(Term::Var(left), Term::Var(right), _) if left == "string" && right == "bool"
+        )));
+    }
+
+    #[test]
+    fn pattern_failure_names_the_escaping_variable() {
+        let unifier = generate_unifier();
+        let mut search = unifier.search(parse_problem("λx:*. λy:*. I x =? λx:*. λy:*. y").unwrap()).unwrap();
+
+        assert_eq!(search.next(), None);
+        assert!(matches!(search.clashes(), [Clash::Scope(meta, variable, _)] if meta == "I" && variable == "y"));
+    }
+
+    #[test]
+    fn bindings_know_the_constraints_that_forced_them() {
+        let unifier = generate_unifier();
+        let problem = parse_problem("option (I u32) =? option (option u32) ∧ J bool =? result bool bool").unwrap();
+
+        let solutions = unifier.solve(problem.clone()).unwrap().0;
+
+        assert!(!solutions.0.is_empty());
+        for solution in &solutions.0 {
+            for (substitution, sources) in solution.sources(&problem) {
+                match substitution.name.as_str() {
+                    "I" => assert_eq!(sources, vec![&problem.0[0]]),
+                    "J" => assert_eq!(sources, vec![&problem.0[1]]),
+                    name => panic!("unexpected binding for {}", name),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn provenance_follows_substitutions_into_other_constraints() {
+        let unifier = generate_unifier();
+        let problem = parse_problem("I u32 =? option u32 ∧ J (I u32) =? option (option u32)").unwrap();
+
+        let solutions = unifier.solve(problem.clone()).unwrap().0;
+
+        let solution = solutions.0.iter()
+            .find(|solution| solution.0.iter().any(|substitution| substitution.name == "J" && substitution.provenance.0.len() == 2))
+            .expect("a binding of J that depends on both constraints");
+        let (_, sources) = solution.sources(&problem).into_iter().find(|(substitution, _)| substitution.name == "J").unwrap();
+        assert_eq!(sources, vec![&problem.0[0], &problem.0[1]]);
+    }
+
+    #[test]
+    fn node_limit_keeps_the_solutions_found_so_far() {
+        let unifier = generate_unifier().with_limits(SearchLimits { max_nodes: Some(4), ..SearchLimits::default() });
+
+        let (solutions, outcome) = unifier.solve(parse_problem("I (L u32) =? option (option u32)").unwrap()).unwrap();
+
+        assert!(!solutions.0.is_empty());
+        assert_eq!(outcome, SearchOutcome::Incomplete);
+    }
+
+    #[test]
+    fn limits_that_are_not_reached_give_a_complete_search() {
+        let unifier = generate_unifier().with_limits(SearchLimits { max_depth: Some(10), max_nodes: Some(1000), max_term_size: Some(100) });
+        let problem = parse_problem("I (L u32) =? option (option u32)").unwrap();
+
+        let (solutions, outcome) = unifier.solve(problem.clone()).unwrap();
+
+        assert_eq!(solutions, generate_unifier().solve(problem).unwrap().0);
+        assert_eq!(outcome, SearchOutcome::Complete);
+    }
+
+    #[test]
+    fn flex_flex_constraints_are_residual() {
+        let solutions = run("I u32 =? L u32");
+
+        assert_eq!(solutions.0.len(), 1);
+        assert!(solutions.0[0].0.is_empty());
+        assert_eq!(solutions.0[0].1, parse_problem("I u32 =? L u32").unwrap().0);
+    }
+
+    #[test]
+    fn flex_rigid_constraints_are_selected_first() {
+        let solutions = run("I u32 =? L u32 ∧ L u32 =? option u32");
+
+        assert!(!solutions.0.is_empty());
+        assert!(solutions.0.iter().all(|solution| solution.1.is_empty()));
+    }
+
+    #[test]
+    fn pattern_has_a_unique_solution() {
+        let solutions = run("λx:*. λy:*. P x y =? λx:*. λy:*. result y x");
+
+        assert_eq!(solutions.0.len(), 1);
+        assert_eq!(solutions.0[0].0[0].with, parse_term("λq:*. λr:*. result r q").unwrap());
+    }
+
+    #[test]
+    fn pattern_prunes_other_metavariables() {
+        let solutions = run("λx:*. λy:*. I x =? λx:*. λy:*. result x (L y)");
+
+        assert_eq!(solutions.0.len(), 1);
+        assert_eq!(solutions.0[0].0.len(), 2);
+        assert!(solutions.0[0].1.is_empty());
+    }
+
+    #[test]
+    fn pattern_only_prunes_metavariables_applied_to_bound_variables() {
+        let mut unifier = generate_unifier();
+        unifier.declare("c", parse_type("*").unwrap());
+        unifier.declare("Y", parse_type("* -> (* -> *) -> *").unwrap());
+        let problem = parse_problem("λx:*. λy:*. X x =? λx:*. λy:*. option (Y y (λz:*. c))").unwrap();
+
+        let solutions = unifier.solve(problem).unwrap().0;
+        let expected = solution(&[("X", "λx:*. option c"), ("Y", "λa:*. λf:* -> *. f a")]);
+
+        println!("Solutions: {}", solutions);
+        assert!(solutions.0.iter().any(|general| expected.is_instance_of(general)));
+    }
+
+    #[test]
+    fn pattern_without_solutions() {
+        let solutions = run("λx:*. λy:*. I x =? λx:*. λy:*. result x y");
+
+        assert!(solutions.0.is_empty());
+    }
+
+    #[test]
+    fn pattern_with_lambdas_works_with_every_priority() {
+        // Arrange
+        let mut unifier = generate_unifier();
+        unifier.declare("app", parse_type("(* -> *) -> * -> *").unwrap());
+        let problems = [
+            parse_problem("λx:*. I x =? λx:*. app (λz:*. z) x").unwrap(),
+            parse_problem("λx:*. I x =? λx:*. app (λz:*. option z) x").unwrap(),
+        ];
+
+        for problem in problems {
+            // Act
+            let filtered = crate::PRIORITIES.iter()
+                .map(|(_, priority)| unifier.solve_with_priorities(problem.clone(), &[*priority]).unwrap())
+                .collect::<Vec<_>>();
+            let best = unifier.solve_best(problem.clone()).unwrap();
+
+            // Assert
+            for solutions in filtered {
+                assert_eq!(solutions.0.len(), 1);
+            }
+            assert!(best.is_ok());
+        }
+    }
+
+    #[test]
+    fn numbered_metavariables_of_the_user_are_solved() {
+        let solutions = run("?3 u32 =? option u32 ∧ λ1000:*. ?4 1000 =? λ1000:*. result 1000 u32");
+
+        assert!(!solutions.0.is_empty());
+        for solution in &solutions.0 {
+            let names = solution.0.iter().map(|substitution| substitution.name.as_str()).collect::<Vec<_>>();
+            assert!(names.contains(&"3") && names.contains(&"4"));
+        }
+        assert!(crate::datatype::generate_fresh_var().parse::<u32>().unwrap() > 1000);
+    }
+
+    #[test]
+    fn problems_are_normalized_on_entry() {
+        let redex = run("(λx:*. option x) u32 =? I u32");
+        let normal = run("option u32 =? I u32");
+
+        assert_eq!(redex, normal);
+    }
+
+    #[test]
+    fn problems_are_eta_expanded_on_entry() {
+        let solutions = run("option =? λx:*. I x");
+
+        assert_eq!(solutions.0.len(), 1);
+        assert_eq!(solutions.0[0].0[0].with, parse_term("λj:*. option j").unwrap());
+    }
+
+    #[test]
+    fn typed_solving_rejects_ill_typed_problems() {
+        let problem = parse_problem("option u32 =? option").unwrap();
+        let unifier = generate_unifier();
+
+        let result = unifier.solve(problem.clone());
+
+        assert!(matches!(result, Err(TypeError::Mismatch(..))));
+        assert!(unifier.solve_unnamed(problem.clone()).is_err());
+        assert!(unifier.solve_with_priorities(problem.clone(), &[existence]).is_err());
+        assert!(unifier.solve_best(problem.clone()).is_err());
+        assert!(unifier.search(problem).is_err());
+    }
+
+    #[test]
+    fn imitation_arguments_are_eta_long() {
+        let mut unifier = generate_unifier();
+        unifier.declare("app", parse_type("(* -> *) -> * -> *").unwrap());
+
+        let solutions = unifier.solve(parse_problem("I u32 =? app (λz:*. option z) u32").unwrap()).unwrap().0;
+
+        println!("Solutions: {}", solutions);
+        assert_eq!(solutions.0.len(), 2);
+        assert!(solutions.0.iter().any(|found| found.alpha_equivalent(&solution(&[("I", "λa:*. app (λz:*. option z) a")]))));
+        assert!(solutions.0.iter().any(|found| found.alpha_equivalent(&solution(&[("I", "λa:*. app (λz:*. option z) u32")]))));
+    }
+
+    #[test]
+    fn imitation_binds_every_argument_of_the_type() {
+        let mut unifier = generate_unifier();
+        unifier.declare("app", parse_type("(* -> *) -> * -> *").unwrap());
+        unifier.declare("X", parse_type("(* -> *) -> *").unwrap());
+
+        let solutions = unifier.solve(parse_problem("X option =? app option u32").unwrap()).unwrap().0;
+
+        println!("Solutions: {}", solutions);
+        assert!(solutions.0.iter().any(|found| found.alpha_equivalent(&solution(&[("X", "λf:* -> *. app (λz:*. option z) u32")]))));
+    }
+
+    #[test]
+    fn typed_projection_applies_higher_order_arguments() {
+        let solutions = generate_unifier().solve(parse_problem("X option =? option u32").unwrap()).unwrap().0;
+        let expected = parse_term("λf:* -> *. f u32").unwrap();
+
+        println!("Solutions: {}", solutions);
+        assert_eq!(solutions.0.len(), 2);
+        assert!(solutions.0.iter().any(|solution| solution.0[0].with.alpha_equivalent(&expected)));
+    }
+
+    #[test]
+    fn self_contained_input_declares_its_signature() {
+        let (context, problem) = parse_input("
+            const u32 : *;
+            const option : * -> *;
+            meta X : (* -> *) -> *;
+            X option =? option u32
+        ").unwrap();
+
+        let solutions = Unifier::new(context.typing_context).solve(problem.clone()).unwrap().0;
+        let typed = generate_unifier().solve(problem).unwrap().0;
+
+        println!("Solutions: {}", solutions);
+        assert_eq!(solutions.0.len(), typed.0.len());
+        for (solution, expected) in solutions.0.iter().zip(&typed.0) {
+            assert!(solution.0[0].with.alpha_equivalent(&expected.0[0].with));
+        }
+    }
+
+    #[test]
+    fn problem_file_examples() {
+        let specs = load_problems(concat!(env!("CARGO_MANIFEST_DIR"), "/problems/examples.hou")).unwrap();
+
+        for spec in specs {
+            let (solutions, outcome) = spec.unifier().solve(spec.problem.clone()).unwrap();
+
+            println!("Problem {}: {}", spec.name, spec.problem);
+            println!("Solutions: {}", solutions);
+            assert!(spec.is_expected(&solutions), "unexpected solutions for {}", spec.name);
+            assert_eq!(outcome, SearchOutcome::Complete, "the search for {} was cut off", spec.name);
+        }
+    }
+
+    #[test]
+    fn traced_search_records_the_tree() {
+        let unifier = generate_unifier();
+        let mut search = unifier.search(parse_problem("I u32 =? option u32").unwrap()).unwrap().with_trace();
+
+        let solutions = search.by_ref().count();
+        let trace = search.trace().unwrap();
+
+        println!("{}", trace);
+        assert!(matches!(trace.0[0].outcome, NodeOutcome::Matched(_)));
+        assert_eq!(trace.0.iter().filter(|node| matches!(node.outcome, NodeOutcome::Solved(_))).count(), solutions);
+        assert!(trace.0.iter().skip(1).all(|node| node.parent.is_some() && node.substitution.is_some()));
+    }
+
+    #[test]
+    fn traced_solutions_lead_back_to_their_steps() {
+        let unifier = generate_unifier();
+        let (solutions, outcome, trace) = unifier.solve_traced(parse_problem("I u32 =? option u32").unwrap()).unwrap();
+
+        assert_eq!(outcome, SearchOutcome::Complete);
+        assert_eq!(trace.0[0].simplified, Some(parse_problem("I u32 =? option u32").unwrap()));
+
+        for index in 0..solutions.0.len() {
+            let node = trace.0.iter().position(|node| node.outcome == NodeOutcome::Solved(index)).unwrap();
+            let path = trace.path(node);
+
+            assert_eq!(path[0], 0);
+            assert_eq!(*path.last().unwrap(), node);
+            assert!(path.iter().skip(1).all(|&step| trace.0[step].substitution.is_some()));
+        }
+
+        let steps = trace.children(0).iter()
+            .map(|&child| trace.0[child].substitution.as_ref().unwrap().0)
+            .collect::<Vec<_>>();
+        assert_eq!(steps, vec![Step::Imitation, Step::Projection]);
+    }
+
+    #[test]
+    fn trace_exports_to_dot() {
+        let unifier = generate_unifier();
+        let (_, _, trace) = unifier.solve_traced(parse_problem("I u32 =? option u32").unwrap()).unwrap();
+
+        let dot = trace.to_dot();
+
+        println!("{}", dot);
+        assert!(dot.starts_with("digraph search {"));
+        assert!(dot.trim_end().ends_with('}'));
+        assert_eq!(dot.matches(" -> n").count(), trace.0.len() - 1);
+        assert!(dot.contains("imitation"));
+        assert!(dot.contains("solution 1"));
+        assert!(!dot.contains('\x1b'));
+    }
 
     #[test]
     fn parse_and_print() {
-        println!("{}   ", parse_term("λx:*. λy:*. N"));
-        println!("{:#?}", parse_term("λx:*. λy:*. N"));
-        println!("{}   ", parse_term("N O P E"));
-        println!("{:#?}", parse_term("N O P E"));
-        println!("{}   ", parse_term("N O (P E)"));
-        println!("{:#?}", parse_term("N O (P E)"));
-        println!("{}   ", parse_term("N (O P E)"));
-        println!("{:#?}", parse_term("N (O P E)"));
-        println!("{}   ", parse_term("N (λx:*. n)"));
-        println!("{:#?}", parse_term("N (λx:*. n)"));
-        println!("{}   ", parse_term("(λx:*. λy:*. n h) N"));
-        println!("{:#?}", parse_term("(λx:*. λy:*. n h) N"));
-        println!("{}   ", parse_term("(λx:*. (λy:*. n) h) N"));
-        println!("{:#?}", parse_term("(λx:*. (λy:*. n) h) N"));
-        println!("{}   ", parse_constraint("I u32 =? option (option u32)"));
-        println!("{:#?}", parse_constraint("I u32 =? option (option u32)"));
-        println!("{}   ", parse_problem("I u32 =? option u32 ∧ I string =? option bool"));
-        println!("{:#?}", parse_problem("I u32 =? option u32 ∧ I string =? option bool"));
+        println!("{}   ", parse_term("λx:*. λy:*. N").unwrap());
+        println!("{:#?}", parse_term("λx:*. λy:*. N").unwrap());
+        println!("{}   ", parse_term("N O P E").unwrap());
+        println!("{:#?}", parse_term("N O P E").unwrap());
+        println!("{}   ", parse_term("N O (P E)").unwrap());
+        println!("{:#?}", parse_term("N O (P E)").unwrap());
+        println!("{}   ", parse_term("N (O P E)").unwrap());
+        println!("{:#?}", parse_term("N (O P E)").unwrap());
+        println!("{}   ", parse_term("N (λx:*. n)").unwrap());
+        println!("{:#?}", parse_term("N (λx:*. n)").unwrap());
+        println!("{}   ", parse_term("(λx:*. λy:*. n h) N").unwrap());
+        println!("{:#?}", parse_term("(λx:*. λy:*. n h) N").unwrap());
+        println!("{}   ", parse_term("(λx:*. (λy:*. n) h) N").unwrap());
+        println!("{:#?}", parse_term("(λx:*. (λy:*. n) h) N").unwrap());
+        println!("{}   ", parse_constraint("I u32 =? option (option u32)").unwrap());
+        println!("{:#?}", parse_constraint("I u32 =? option (option u32)").unwrap());
+        println!("{}   ", parse_problem("I u32 =? option u32 ∧ I string =? option bool").unwrap());
+        println!("{:#?}", parse_problem("I u32 =? option u32 ∧ I string =? option bool").unwrap());
+    }
+
+    #[test]
+    fn display_is_plain_by_default() {
+        let solutions = run("I u32 =? option u32");
+
+        assert!(!solutions.to_string().contains('\x1b'));
+        assert_eq!(parse_constraint("I u32 =? option u32").unwrap().to_string(), "I u32 =? option u32");
+    }
+
+    #[test]
+    fn ansi_printing_colours_the_output() {
+        let constraint = parse_constraint("I u32 =? option u32").unwrap();
+
+        let ansi = constraint.print_in(PrintMode::Ansi);
+        let plain = constraint.print_in(PrintMode::Plain);
+
+        assert!(ansi.contains("\x1b["));
+        assert_eq!(plain, "I u32 =? option u32");
+    }
+
+    #[test]
+    fn plain_printing_keeps_names_with_angle_brackets() {
+        let constraint = Constraint::new(
+            Term::App(Box::new(Term::Meta("I".to_string())), Box::new(Term::Var("a<b".to_string()))),
+            Term::Var("c>d".to_string()),
+        );
+
+        assert_eq!(constraint.print_in(PrintMode::Plain), "I a<b =? c>d");
+    }
+
+    #[test]
+    fn parse_and_print_higher_order_types() {
+        for input in ["*", "* -> *", "* -> * -> *", "(* -> *) -> *", "(* -> *) -> (* -> *) -> *", "((* -> *) -> *) -> *"] {
+            let typ = parse_type(input).unwrap();
+            let printed = typ.print();
+
+            println!("{}   ", typ);
+            assert_eq!(printed, input);
+            assert_eq!(parse_type(&printed).unwrap(), typ);
+        }
+
+        assert_eq!(parse_type("* -> (* -> *)").unwrap(), parse_type("* -> * -> *").unwrap());
+        assert_ne!(parse_type("(* -> *) -> *").unwrap(), parse_type("* -> * -> *").unwrap());
     }
 
     #[test]
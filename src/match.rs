@@ -1,36 +1,60 @@
 use crate::datatype::{Constraint, Context, generate_fresh_var, Substitution, Term, Type};
+use crate::trace::Step;
 
-pub fn match_(context: Context, constraint: Constraint) -> Vec<Substitution> {
+/// The imitation, if there is one, followed by the projections for the flex-rigid constraint.
+pub fn match_(context: Context, constraint: Constraint) -> Vec<(Step, Substitution)> {
     let mut res = vec![];
-    res.push(imitation(&context, &constraint));
-    res.append(&mut projection(&context, &constraint));
+    res.extend(imitation(&context, &constraint).map(|substitution| (Step::Imitation, substitution)));
+    res.extend(projection(&context, &constraint).into_iter().map(|substitution| (Step::Projection, substitution)));
     res
 }
 
-pub fn imitation(_context: &Context, constraint: &Constraint) -> Substitution {
-    let (_, x, l_tail) = constraint.left.split();
-    let (_, h, r_tail) = constraint.right.split();
+/// The flex side of the constraint: the metavariable and its number of arguments, and the
+/// rigid side: the head, its number of arguments and whether the head is bound by the rigid side.
+fn orient(constraint: &Constraint) -> (Term, usize, Term, usize, bool) {
+    let (l_lambda, x, l_tail) = constraint.left.split();
+    let (r_lambda, h, r_tail) = constraint.right.split();
 
-    let x_argument_count = l_tail.len();
-    let h_argument_count = r_tail.len();
+    match x {
+        Term::Meta(_) => {
+            let bound = h.binding_index(&r_lambda).is_some();
+            (x, l_tail.len(), h, r_tail.len(), bound)
+        }
+        _ => {
+            let bound = x.binding_index(&l_lambda).is_some();
+            (h, r_tail.len(), x, l_tail.len(), bound)
+        }
+    }
+}
 
-    let function_constructed = match x {
-        Term::Meta(_) => construct_imitation_function(x_argument_count, h_argument_count, h.clone()),
-        _ => construct_imitation_function(h_argument_count, x_argument_count, x.clone())
-    };
+/// The types of the binders of a function for the metavariable. These are the argument types of
+/// the metavariable if its type is known, such that the function takes all of its arguments even
+/// if it is applied to fewer, and `*` for the arguments of unknown type.
+fn binder_types(context: &Context, meta: &Term, count: usize) -> Vec<Type> {
+    let arguments = context.typing_context.get(&meta.get_name()).map(Type::arguments).unwrap_or_default();
 
-    match x {
-        Term::Meta(_) => Substitution { name: x.get_name(), with: function_constructed },
-        _ => Substitution { name: h.get_name(), with: function_constructed }
+    (0..count.max(arguments.len())).map(|index| arguments.get(index).cloned().unwrap_or(Type::Star)).collect()
+}
+
+/// The imitation of the rigid head. There is none if the head is bound by the rigid side, as the
+/// solution for the metavariable can not refer to that binder.
+pub fn imitation(context: &Context, constraint: &Constraint) -> Option<Substitution> {
+    let (x, x_argument_count, h, h_argument_count, bound) = orient(constraint);
+    if bound {
+        return None;
     }
 
+    let binder_types = binder_types(context, &x, x_argument_count);
+    let function_constructed = construct_imitation_function(&binder_types, h_argument_count, h);
+
+    Some(Substitution::new(x.get_name(), function_constructed))
 }
 
-fn construct_imitation_function(x_argument_count: usize, h_argument_count: usize, h: Term) -> Term {
+fn construct_imitation_function(binder_types: &[Type], h_argument_count: usize, h: Term) -> Term {
     let mut fresh_vars = vec![];
     let mut builder = h.clone();
 
-    for _ in 0..x_argument_count {
+    for _ in binder_types {
         fresh_vars.push(generate_fresh_var())
     }
 
@@ -42,51 +66,59 @@ fn construct_imitation_function(x_argument_count: usize, h_argument_count: usize
         builder = Term::App(Box::new(builder), Box::new(meta_builder))
     }
 
-    for fresh_var in fresh_vars.iter().rev() {
-        //TODO change Type::Star?
-        builder = Term::Abs(fresh_var.clone(), Type::Star, Box::new(builder));
+    for (fresh_var, typ) in fresh_vars.iter().zip(binder_types).rev() {
+        builder = Term::Abs(fresh_var.clone(), typ.clone(), Box::new(builder));
     }
 
     builder
 }
 
-pub fn projection(_context: &Context, constraint: &Constraint) -> Vec<Substitution> {
-    let (_, x, l_head) = constraint.left.split();
-    let (_, h, r_head) = constraint.right.split();
-    let x_argument_count = l_head.len();
-    let h_argument_count = r_head.len();
+pub fn projection(context: &Context, constraint: &Constraint) -> Vec<Substitution> {
+    let (x, x_argument_count, _, _, _) = orient(constraint);
+
+    let binder_types = binder_types(context, &x, x_argument_count);
+
+    // The result type of the function, if the type of the metavariable is known.
+    let target = context.typing_context.get(&x.get_name()).map(|typ| typ.result(binder_types.len()));
 
     let mut substitutions = vec![];
 
-    for index in 0..x_argument_count {
-        let substitution = match x {
-            Term::Meta(_) => {
-                let projected_function = construct_projection_function(index, x_argument_count);
-                Substitution { name: x.get_name(), with: projected_function }
-            }
-            _ => {
-                let projected_function = construct_projection_function(index, h_argument_count);
-                Substitution { name: h.get_name(), with: projected_function }
-            }
+    for index in 0..binder_types.len() {
+        // A projection is only possible if the projected argument can result in the target type.
+        let argument_count = match &target {
+            Some(target) => match binder_types[index].arguments_to(target) {
+                Some(argument_count) => argument_count,
+                None => continue,
+            },
+            None => 0
         };
-        substitutions.push(substitution);
+
+        let projected_function = construct_projection_function(index, &binder_types, argument_count);
+        substitutions.push(Substitution::new(x.get_name(), projected_function));
     }
 
     substitutions
 }
 
-fn construct_projection_function(index: usize, length: usize) -> Term {
+fn construct_projection_function(index: usize, binder_types: &[Type], argument_count: usize) -> Term {
     let mut fresh_vars = vec![];
 
-    for _ in 0.. length{
+    for _ in binder_types {
         fresh_vars.push(generate_fresh_var())
     }
 
     let mut builder = Term::Var(fresh_vars[index].clone());
 
-    for fresh_var in fresh_vars.iter().rev() {
-        //TODO change Type::Star?
-        builder = Term::Abs(fresh_var.clone(), Type::Star, Box::new(builder))
+    for _ in 0..argument_count {
+        let mut meta_builder = Term::Meta(generate_fresh_var());
+        for fresh_var in &fresh_vars {
+            meta_builder = Term::App(Box::new(meta_builder), Box::new(Term::Var(fresh_var.clone())))
+        }
+        builder = Term::App(Box::new(builder), Box::new(meta_builder))
+    }
+
+    for (fresh_var, typ) in fresh_vars.iter().zip(binder_types).rev() {
+        builder = Term::Abs(fresh_var.clone(), typ.clone(), Box::new(builder))
     }
 
     builder
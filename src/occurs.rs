@@ -0,0 +1,88 @@
+use std::collections::HashSet;
+use crate::datatype::{Clash, Constraint, generate_fresh_var, Substitution, Term};
+use crate::substs::term_substitution;
+
+/// Check whether the flex side `λx̄. X t̄` of a flex-rigid constraint occurs on a rigid path of
+/// the other side. Any solution would then make a term equal to a proper subterm of itself.
+///
+/// This is only sound for the exact flex term, as `X` might drop or change its arguments.
+/// Constraints in the pattern fragment get the stronger check in `pattern_unify`, where any
+/// rigid occurrence of `X` is a clash.
+pub fn occurs_check(constraint: &Constraint) -> Option<Clash> {
+    let (_, l_head, _) = constraint.left.split();
+    let (_, r_head, _) = constraint.right.split();
+
+    let (meta, flex, rigid) = match (&l_head, &r_head) {
+        (Term::Meta(_), _) if r_head.is_rigid() => (l_head, &constraint.left, &constraint.right),
+        (_, Term::Meta(_)) if l_head.is_rigid() => (r_head, &constraint.right, &constraint.left),
+        _ => return None,
+    };
+
+    let (flex_binders, flex_body) = strip_binders(flex);
+    let (rigid_binders, rigid_body) = strip_binders(rigid);
+
+    // The sides are only comparable when they are under the same number of binders. The binders
+    // of the rigid side are renamed to the ones of the flex side, through fresh names in case the
+    // two sides use the same names in a different order.
+    if flex_binders.len() != rigid_binders.len() {
+        return None;
+    }
+
+    let fresh_vars = rigid_binders.iter().map(|_| generate_fresh_var()).collect::<Vec<_>>();
+    let mut rigid_body = rigid_body.clone();
+    for (binder, fresh_var) in rigid_binders.iter().zip(&fresh_vars) {
+        rigid_body = term_substitution(rigid_body, Substitution::new(*binder, Term::Var(fresh_var.clone())));
+    }
+    for (fresh_var, binder) in fresh_vars.iter().zip(&flex_binders) {
+        rigid_body = term_substitution(rigid_body, Substitution::new(fresh_var, Term::Var(binder.to_string())));
+    }
+
+    if occurs_rigidly(flex_body, &rigid_body, &flex_body.free_vars(), &HashSet::new()) {
+        Some(Clash::Occurs(meta.get_name(), constraint.clone()))
+    } else {
+        None
+    }
+}
+
+fn strip_binders(term: &Term) -> (Vec<&String>, &Term) {
+    let mut current = term;
+    let mut binders = vec![];
+    while let Term::Abs(s, _, inner) = current {
+        binders.push(s);
+        current = inner;
+    }
+    (binders, current)
+}
+
+fn occurs_rigidly(needle: &Term, term: &Term, needle_free: &HashSet<String>, bound: &HashSet<String>) -> bool {
+    if term.alpha_equivalent(needle) && needle_free.is_disjoint(bound) {
+        return true;
+    }
+
+    match term {
+        Term::Abs(s, _, inner) => {
+            let mut new_bound = bound.clone();
+            new_bound.insert(s.clone());
+            occurs_rigidly(needle, inner, needle_free, &new_bound)
+        }
+        _ => {
+            let (_, head, arguments) = term.split();
+            head.is_rigid() && arguments.iter().any(|argument| occurs_rigidly(needle, argument, needle_free, bound))
+        }
+    }
+}
+
+
+#[test]
+fn test_occurs_check_under_renamed_binders() {
+    // Arrange
+    let same = crate::parse::parse_constraint("λx:*. I (option x) =? λx:*. option (I (option x))").unwrap();
+    let renamed = crate::parse::parse_constraint("λx:*. I (option x) =? λy:*. option (I (option y))").unwrap();
+    let swapped = crate::parse::parse_constraint("λx:*. λy:*. I x y =? λy:*. λx:*. option (I y x)").unwrap();
+
+    // Act
+    let actual = (occurs_check(&same), occurs_check(&renamed), occurs_check(&swapped));
+
+    // Assert
+    assert!(matches!(actual, (Some(Clash::Occurs(..)), Some(Clash::Occurs(..)), Some(Clash::Occurs(..)))));
+}
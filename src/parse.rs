@@ -1,20 +1,318 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
 use lalrpop_util::lalrpop_mod;
-use crate::datatype::{Constraint, Problem, Term, Type};
+use crate::datatype::{Constraint, Context, Declaration, Problem, Term, Type};
+use crate::spec::ProblemSpec;
 
-lalrpop_mod!(parser);
+lalrpop_mod!(#[allow(clippy::all)] parser);
 
-pub fn parse_problem(s: &str) -> Problem {
-    parser::ProblemParser::new().parse(s).unwrap()
+type LalrpopError<'input> = lalrpop_util::ParseError<usize, parser::Token<'input>, &'static str>;
+
+/// A position in the input. Both the line and the column start at 1, and the column counts
+/// characters rather than bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// An error in the input, with the span of the offending token and the tokens the parser
+/// expected instead.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ParseError {
+    pub message: String,
+    pub start: Location,
+    pub end: Location,
+    pub expected: Vec<String>,
+    /// The line of the input containing the start of the error, used for rendering.
+    pub source_line: String,
+}
+
+/// Parse a problem preceded by its declarations, as in
+/// `const u32 : *; const option : * -> *; meta I : * -> *; I u32 =? option u32`. The declared
+/// constants and metavariables make up the typing context of the returned context.
+pub fn parse_input(s: &str) -> Result<(Context, Problem), ParseError> {
+    let (declarations, problem) = parser::InputParser::new().parse(s).map_err(|error| ParseError::new(s, error))?;
+    Ok((context(declarations), problem))
+}
+
+/// Parse a problem file: the declarations shared by all problems, followed by the named problems.
+/// The declarations of a problem take precedence over the shared ones.
+pub fn parse_problem_file(s: &str) -> Result<Vec<ProblemSpec>, ParseError> {
+    let (declarations, mut specs) = parser::ProblemFileParser::new().parse(s).map_err(|error| ParseError::new(s, error))?;
+    let signature = context(declarations).typing_context;
+
+    for spec in &mut specs {
+        for (name, typ) in &signature {
+            spec.context.typing_context.entry(name.clone()).or_insert_with(|| typ.clone());
+        }
+    }
+
+    Ok(specs)
+}
+
+pub fn parse_declaration(s: &str) -> Result<Declaration, ParseError> {
+    parser::DeclarationParser::new().parse(s).map_err(|error| ParseError::new(s, error))
+}
+
+pub fn parse_problem(s: &str) -> Result<Problem, ParseError> {
+    parser::ProblemParser::new().parse(s).map_err(|error| ParseError::new(s, error))
+}
+
+pub fn parse_constraint(s: &str) -> Result<Constraint, ParseError> {
+    parser::ConstraintParser::new().parse(s).map_err(|error| ParseError::new(s, error))
+}
+
+pub fn parse_term(s: &str) -> Result<Term, ParseError> {
+    parser::TermParser::new().parse(s).map_err(|error| ParseError::new(s, error))
+}
+
+pub fn parse_type(s: &str) -> Result<Type, ParseError> {
+    parser::TypeParser::new().parse(s).map_err(|error| ParseError::new(s, error))
+}
+
+impl ParseError {
+    fn new(source: &str, error: LalrpopError) -> ParseError {
+        let (message, start, end, expected) = match error {
+            LalrpopError::InvalidToken { location } =>
+                ("invalid token".to_string(), location, next_char(source, location), vec![]),
+            LalrpopError::UnrecognizedEOF { location, expected } =>
+                ("unexpected end of input".to_string(), location, location, expected),
+            LalrpopError::UnrecognizedToken { token: (start, token, end), expected } =>
+                (format!("unexpected token `{}`", token), start, end, expected),
+            LalrpopError::ExtraToken { token: (start, token, end) } =>
+                (format!("extra token `{}`", token), start, end, vec![]),
+            LalrpopError::User { error } =>
+                (error.to_string(), 0, 0, vec![]),
+        };
+
+        let start = location(source, start);
+
+        ParseError {
+            message,
+            start,
+            end: location(source, end),
+            expected,
+            source_line: source.lines().nth(start.line - 1).unwrap_or("").to_string(),
+        }
+    }
+
+    /// Render the error as a diagnostic with a caret under the offending position.
+    pub fn render(&self) -> String {
+        let line_number = self.start.line.to_string();
+        let padding = " ".repeat(line_number.len());
+
+        let width = if self.end.line == self.start.line && self.end.column > self.start.column {
+            self.end.column - self.start.column
+        } else {
+            1
+        };
+
+        let mut rendered = format!(
+            "error: {}\n{}--> {}:{}\n{} |\n{} | {}\n{} | {}{}",
+            self.message,
+            padding, self.start.line, self.start.column,
+            padding,
+            line_number, self.source_line,
+            padding, " ".repeat(self.start.column - 1), "^".repeat(width)
+        );
+
+        if !self.expected.is_empty() {
+            rendered.push_str(&format!("\n{} = expected one of {}", padding, self.expected.join(", ")));
+        }
+
+        rendered
+    }
 }
 
-pub fn parse_constraint(s: &str) -> Constraint {
-    parser::ConstraintParser::new().parse(s).unwrap()
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render())
+    }
 }
 
-pub fn parse_term(s: &str) -> Term {
-    parser::TermParser::new().parse(s).unwrap()
+impl std::error::Error for ParseError {}
+
+/// A fresh context with the declared constants and metavariables as its typing context.
+pub(crate) fn context(declarations: Vec<Declaration>) -> Context {
+    let typing_context = declarations.into_iter()
+        .map(|declaration| match declaration {
+            Declaration::Const(name, typ) | Declaration::Meta(name, typ) => (name, typ)
+        })
+        .collect();
+
+    Context {
+        typing_context,
+        substitutions: vec![],
+        solutions: Rc::new(RefCell::new(vec![])),
+        name_map: HashMap::new(),
+    }
 }
 
-pub fn parse_type(s: &str) -> Type {
-    parser::TypeParser::new().parse(s).unwrap()
-}
\ No newline at end of file
+/// Convert a byte offset in the source to a line and column.
+fn location(source: &str, offset: usize) -> Location {
+    let before = &source[..offset.min(source.len())];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+
+    Location {
+        line,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+fn next_char(source: &str, offset: usize) -> usize {
+    offset + source[offset.min(source.len())..].chars().next().map_or(0, char::len_utf8)
+}
+
+
+#[test]
+fn test_parse_error_location() {
+    // Arrange
+    let input = "I u32 =? =? option u32";
+
+    // Act
+    let actual = parse_problem(input).unwrap_err();
+
+    // Assert
+    assert_eq!(actual.start, Location { line: 1, column: 10 });
+    assert_eq!(actual.end, Location { line: 1, column: 12 });
+    assert!(!actual.expected.is_empty());
+    assert!(actual.render().contains("I u32 =? =? option u32\n  |          ^^"), "{}", actual.render());
+}
+
+#[test]
+fn test_parse_error_counts_characters() {
+    // Arrange
+    let input = "I u32 =? λy:*. ]";
+
+    // Act
+    let actual = parse_problem(input).unwrap_err();
+
+    // Assert
+    assert_eq!(actual.start, Location { line: 1, column: 16 });
+    assert_eq!(actual.message, "invalid token");
+}
+
+#[test]
+fn test_parse_error_at_end_of_input() {
+    // Arrange
+    let input = "I u32 =?";
+
+    // Act
+    let actual = parse_constraint(input).unwrap_err();
+
+    // Assert
+    assert_eq!(actual.message, "unexpected end of input");
+    assert_eq!(actual.start, Location { line: 1, column: 9 });
+}
+
+#[test]
+fn test_parse_declarations() {
+    // Arrange
+    let input = "const u32 : *; const option : * -> *;\nmeta I : * -> *;\nI u32 =? option u32";
+
+    // Act
+    let (context, problem) = parse_input(input).unwrap();
+
+    // Assert
+    assert_eq!(context.typing_context.len(), 3);
+    assert_eq!(context.typing_context["option"], parse_type("* -> *").unwrap());
+    assert_eq!(context.typing_context["I"], parse_type("* -> *").unwrap());
+    assert_eq!(problem, parse_problem("I u32 =? option u32").unwrap());
+}
+
+#[test]
+fn test_parse_declaration_keywords_as_names() {
+    // Arrange
+    let input = "const const : * -> *; const meta : *; (const meta) =? I const ∧ const meta =? const (I meta)";
+
+    // Act
+    let (context, problem) = parse_input(input).unwrap();
+
+    // Assert
+    assert_eq!(context.typing_context["const"], parse_type("* -> *").unwrap());
+    assert_eq!(context.typing_context["meta"], Type::Star);
+    assert_eq!(problem, parse_problem("const meta =? I const ∧ const meta =? const (I meta)").unwrap());
+    assert!(parse_input("const u32 : *; const meta =? u32").is_err());
+}
+
+#[test]
+fn test_parse_declaration() {
+    // Arrange
+    let constant = "const fn2 : * -> * -> * -> *;";
+    let meta = "meta P : (* -> *) -> *;";
+
+    // Act
+    let constant = parse_declaration(constant).unwrap();
+    let meta = parse_declaration(meta).unwrap();
+
+    // Assert
+    assert_eq!(constant, Declaration::Const("fn2".to_string(), parse_type("* -> * -> * -> *").unwrap()));
+    assert_eq!(meta, Declaration::Meta("P".to_string(), parse_type("(* -> *) -> *").unwrap()));
+}
+
+#[test]
+fn test_parse_problem_file() {
+    // Arrange
+    let input = "
+        // The shared signature
+        const u32 : *;
+        const option : * -> *;
+
+        problem first {
+            meta I : * -> *; // declared for this problem only
+            I u32 =? option u32;
+            expect I => λx:*. option x;
+            expect I => λx:*. option u32;
+        }
+
+        problem second {
+            const u32 : * -> *;
+            I u32 =? option;
+            expect ∅;
+        }
+
+        problem third {
+            u32 =? u32;
+        }
+    ";
+
+    // Act
+    let actual = parse_problem_file(input).unwrap();
+
+    // Assert
+    assert_eq!(actual.len(), 3);
+    assert_eq!(actual[0].name, "first");
+    assert_eq!(actual[0].context.typing_context["I"], parse_type("* -> *").unwrap());
+    assert_eq!(actual[0].expected.as_ref().map(|expected| expected.0.len()), Some(2));
+    assert!(!actual[1].context.typing_context.contains_key("I"));
+    assert_eq!(actual[1].context.typing_context["u32"], parse_type("* -> *").unwrap());
+    assert_eq!(actual[1].expected.as_ref().map(|expected| expected.0.len()), Some(0));
+    assert_eq!(actual[2].expected, None);
+}
+
+#[test]
+fn test_parse_problem_file_keywords_as_names() {
+    // Arrange
+    let input = "
+        const problem : * -> *;
+        const expect : *;
+
+        problem problem {
+            problem expect =? I expect;
+            expect I => λx:*. problem x;
+        }
+    ";
+
+    // Act
+    let actual = parse_problem_file(input).unwrap();
+
+    // Assert
+    assert_eq!(actual.len(), 1);
+    assert_eq!(actual[0].name, "problem");
+    assert_eq!(actual[0].problem, parse_problem("problem expect =? I expect").unwrap());
+    assert_eq!(actual[0].expected.as_ref().map(|expected| expected.0.len()), Some(1));
+}
@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+use crate::datatype::{Clash, Constraint, generate_fresh_var, Substitution, Term, Type};
+use crate::substs::term_substitution;
+
+/// The result of trying to solve a constraint as a higher-order pattern.
+#[derive(Clone, PartialEq, Debug)]
+pub enum PatternResult {
+    /// The constraint is not a pattern, or can not be solved without branching.
+    NotPattern,
+    /// The constraint is a pattern without any solutions, for the given reason.
+    Clash(Clash),
+    /// The substitution to apply. Either the pruning of an argument of another metavariable, or
+    /// the most general unifier of the constraint.
+    Step(Substitution),
+}
+
+/// Why the inversion of the rigid side stopped.
+enum Stop {
+    /// A variable that the metavariable can not refer to occurs in a rigid position.
+    Clash(String),
+    /// The metavariable itself occurs in a rigid position.
+    Occurs,
+    /// The inversion can not proceed deterministically.
+    Unknown,
+    /// Another metavariable has to drop an argument before the inversion can proceed.
+    Prune(Substitution),
+}
+
+/// Try to solve a flex-rigid constraint `λx̄. X a1 .. an =? λȳ. t` where the arguments `a1 .. an`
+/// are distinct variables bound by `λx̄`. Such a constraint is in Miller's pattern fragment and
+/// has a unique most general unifier, which is found without imitation and projection.
+pub fn pattern_unify(constraint: &Constraint, typing_context: &HashMap<String, Type>) -> PatternResult {
+    let (l_lambda, l_head, l_tail) = constraint.left.split();
+    let (r_lambda, r_head, _) = constraint.right.split();
+
+    let (flex_lambda, meta, arguments, rigid) = match (&l_head, &r_head) {
+        (Term::Meta(_), _) if r_head.is_rigid() => (l_lambda, l_head, l_tail, &constraint.right),
+        (_, Term::Meta(_)) if l_head.is_rigid() => {
+            let (_, _, r_tail) = constraint.right.split();
+            (r_lambda, r_head, r_tail, &constraint.left)
+        }
+        _ => return PatternResult::NotPattern,
+    };
+
+    let flex_binders = binders(&flex_lambda);
+
+    // The position in the flex binders of each argument, which must be distinct bound variables.
+    let mut positions = vec![];
+    for argument in &arguments {
+        let position = match argument {
+            Term::Var(s) => flex_binders.iter().rposition(|(name, _)| name == s),
+            _ => None,
+        };
+
+        match position {
+            Some(position) if !positions.contains(&position) => positions.push(position),
+            _ => return PatternResult::NotPattern,
+        }
+    }
+
+    let mut body = rigid.clone();
+    let mut rigid_binders = vec![];
+    while let Term::Abs(s, _, inner) = body {
+        rigid_binders.push(s);
+        body = *inner;
+    }
+
+    if rigid_binders.len() != flex_binders.len() {
+        return PatternResult::NotPattern;
+    }
+
+    let fresh_vars = positions.iter().map(|_| generate_fresh_var()).collect::<Vec<_>>();
+
+    let mut renaming = HashMap::new();
+    for (position, name) in rigid_binders.iter().enumerate() {
+        let renamed = positions.iter().position(|p| *p == position).map(|index| fresh_vars[index].clone());
+        renaming.insert(name.clone(), renamed);
+    }
+
+    let inversion = Inversion {
+        meta: meta.get_name(),
+        renaming,
+        typing_context,
+    };
+
+    match inversion.invert(&body, &HashSet::new()) {
+        Ok(inverted) => {
+            let mut builder = inverted;
+            for (fresh_var, position) in fresh_vars.iter().zip(positions).rev() {
+                builder = Term::Abs(fresh_var.clone(), flex_binders[position].1.clone(), Box::new(builder));
+            }
+
+            PatternResult::Step(Substitution::new(meta.get_name(), builder))
+        }
+        Err(Stop::Clash(variable)) => PatternResult::Clash(Clash::Scope(meta.get_name(), variable, constraint.clone())),
+        Err(Stop::Occurs) => PatternResult::Clash(Clash::Occurs(meta.get_name(), constraint.clone())),
+        Err(Stop::Unknown) => PatternResult::NotPattern,
+        Err(Stop::Prune(substitution)) => PatternResult::Step(substitution),
+    }
+}
+
+fn binders(lambda: &Term) -> Vec<(String, Type)> {
+    let mut current = lambda;
+    let mut binders = vec![];
+    while let Term::Abs(s, typ, inner) = current {
+        binders.push((s.clone(), typ.clone()));
+        current = inner;
+    }
+    binders
+}
+
+struct Inversion<'a> {
+    meta: String,
+    /// The variables bound by the rigid side, mapped to the fresh variable that replaces them,
+    /// or `None` if the metavariable is not applied to them.
+    renaming: HashMap<String, Option<String>>,
+    typing_context: &'a HashMap<String, Type>,
+}
+
+impl Inversion<'_> {
+    fn invert(&self, term: &Term, bound: &HashSet<String>) -> Result<Term, Stop> {
+        // The binders of the solution get fresh names, like the ones of imitation and projection,
+        // instead of the names written in the problem.
+        if let Term::Abs(s, typ, inner) = term {
+            let fresh_var = generate_fresh_var();
+            let inner = term_substitution(*inner.clone(), Substitution::new(s.clone(), Term::Var(fresh_var.clone())));
+
+            let mut new_bound = bound.clone();
+            new_bound.insert(fresh_var.clone());
+            return Ok(Term::Abs(fresh_var, typ.clone(), Box::new(self.invert(&inner, &new_bound)?)));
+        }
+
+        let (_, head, arguments) = term.split();
+
+        match head {
+            Term::Meta(name) if name == self.meta => Err(Stop::Occurs),
+            Term::Meta(name) => {
+                // Dropping an argument only loses no solutions if the other metavariable is
+                // applied to distinct bound variables, as in a pattern.
+                let prunable = self.is_pattern(&arguments, bound);
+
+                let mut inverted = vec![];
+                for argument in &arguments {
+                    match self.invert(argument, bound) {
+                        Ok(argument) => inverted.push(Some(argument)),
+                        Err(Stop::Clash(_)) if prunable => inverted.push(None),
+                        // In the arguments of another metavariable, the occurrence might be pruned.
+                        Err(Stop::Clash(_) | Stop::Occurs) => return Err(Stop::Unknown),
+                        Err(stop) => return Err(stop),
+                    }
+                }
+
+                if inverted.iter().any(Option::is_none) {
+                    let kept = inverted.iter().map(Option::is_some).collect::<Vec<_>>();
+                    let types = self.typing_context.get(&name).map(Type::arguments).unwrap_or_default();
+                    return Err(Stop::Prune(pruning(&name, &kept, &types)));
+                }
+
+                Ok(apply(Term::Meta(name), inverted.into_iter().flatten()))
+            }
+            Term::Var(name) => {
+                let head = match self.renaming.get(&name) {
+                    Some(_) if bound.contains(&name) => Term::Var(name),
+                    Some(Some(renamed)) => Term::Var(renamed.clone()),
+                    Some(None) => return Err(Stop::Clash(name)),
+                    None => Term::Var(name),
+                };
+
+                let mut inverted = vec![];
+                for argument in &arguments {
+                    inverted.push(self.invert(argument, bound)?);
+                }
+
+                Ok(apply(head, inverted))
+            }
+            _ => unreachable!()
+        }
+    }
+
+    fn is_pattern(&self, arguments: &[Term], bound: &HashSet<String>) -> bool {
+        let mut seen = HashSet::new();
+        arguments.iter().all(|argument| match argument {
+            Term::Var(name) => (bound.contains(name) || self.renaming.contains_key(name)) && seen.insert(name),
+            _ => false,
+        })
+    }
+}
+
+/// Construct `name := λw1 .. wn. Y wi ..` keeping only the arguments marked as kept. The binders
+/// get the argument types of the metavariable, if known, and `*` otherwise.
+fn pruning(name: &str, kept: &[bool], types: &[Type]) -> Substitution {
+    let fresh_vars = kept.iter().map(|_| generate_fresh_var()).collect::<Vec<_>>();
+
+    let arguments = fresh_vars.iter()
+        .zip(kept)
+        .filter(|(_, kept)| **kept)
+        .map(|(fresh_var, _)| Term::Var(fresh_var.clone()));
+
+    let mut builder = apply(Term::Meta(generate_fresh_var()), arguments);
+
+    for (index, fresh_var) in fresh_vars.iter().enumerate().rev() {
+        let typ = types.get(index).cloned().unwrap_or(Type::Star);
+        builder = Term::Abs(fresh_var.clone(), typ, Box::new(builder));
+    }
+
+    Substitution::new(name, builder)
+}
+
+fn apply(head: Term, arguments: impl IntoIterator<Item = Term>) -> Term {
+    arguments.into_iter().fold(head, |builder, argument| Term::App(Box::new(builder), Box::new(argument)))
+}
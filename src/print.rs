@@ -1,69 +1,189 @@
 use std::fmt::{Debug, Display, Formatter};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
 use paris::formatter::colorize_string;
-use crate::datatype::{Constraint, Problem, Solution, SolutionSet, Substitution, Term, Type};
+use crate::datatype::{Clash, Constraint, Problem, Solution, SolutionSet, Substitution, Term, Type};
+use crate::explain::Explanation;
+use crate::search::SearchOutcome;
+use crate::trace::{NodeOutcome, Step, Trace};
+use crate::typing::TypeError;
+
+/// How terms, types and the other datatypes are printed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PrintMode {
+    /// Plain text, without escape codes.
+    Plain,
+    /// Coloured with ANSI escape codes.
+    Ansi,
+    /// Coloured if the output is a terminal and `NO_COLOR` is not set.
+    Auto,
+}
+
+/// The output streams of the program, which are coloured independently in auto mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// The modes used for the two streams, either plain (0) or coloured (1). Plain by default, such
+/// that the output can be compared, logged and parsed back.
+static STDOUT_MODE: AtomicU8 = AtomicU8::new(0);
+static STDERR_MODE: AtomicU8 = AtomicU8::new(0);
+
+/// Whether the stream is coloured in auto mode, decided the first time it is asked.
+static STDOUT_COLORED: OnceLock<bool> = OnceLock::new();
+static STDERR_COLORED: OnceLock<bool> = OnceLock::new();
+
+impl Stream {
+    fn mode(self) -> &'static AtomicU8 {
+        match self {
+            Stream::Stdout => &STDOUT_MODE,
+            Stream::Stderr => &STDERR_MODE,
+        }
+    }
+
+    fn is_colored(self) -> bool {
+        let detect = |is_terminal: bool| is_terminal && std::env::var_os("NO_COLOR").is_none();
+        match self {
+            Stream::Stdout => *STDOUT_COLORED.get_or_init(|| detect(std::io::stdout().is_terminal())),
+            Stream::Stderr => *STDERR_COLORED.get_or_init(|| detect(std::io::stderr().is_terminal())),
+        }
+    }
+}
+
+/// Set the mode used by `print` and `Display` in the whole program. Auto mode is settled here,
+/// separately for standard output and standard error.
+pub fn set_print_mode(mode: PrintMode) {
+    for stream in [Stream::Stdout, Stream::Stderr] {
+        let colored = mode.resolve(stream) == PrintMode::Ansi;
+        stream.mode().store(colored as u8, Ordering::Relaxed);
+    }
+}
+
+/// The mode used by `print` and `Display`, which is the one for standard output.
+pub fn print_mode() -> PrintMode {
+    print_mode_for(Stream::Stdout)
+}
+
+/// The mode for output written to the stream, either plain or ANSI.
+pub fn print_mode_for(stream: Stream) -> PrintMode {
+    match stream.mode().load(Ordering::Relaxed) {
+        1 => PrintMode::Ansi,
+        _ => PrintMode::Plain,
+    }
+}
+
+impl PrintMode {
+    /// The mode for output written to the stream, with auto mode settled.
+    fn resolve(self, stream: Stream) -> PrintMode {
+        match self {
+            PrintMode::Auto if stream.is_colored() => PrintMode::Ansi,
+            PrintMode::Auto => PrintMode::Plain,
+            mode => mode,
+        }
+    }
+}
+
+/// Colour the text with paris tags, such as `<red>`, in ANSI mode. Auto mode is the mode for
+/// standard output. The text is left alone otherwise.
+fn paint(mode: PrintMode, tags: &str, text: &str) -> String {
+    match mode.resolve(Stream::Stdout) {
+        PrintMode::Ansi => colorize_string(format!("{}{}</>", tags, text)),
+        _ => text.to_string(),
+    }
+}
 
 impl Term {
     pub fn print(&self) -> String {
+        self.print_in(print_mode())
+    }
+
+    pub fn print_in(&self, mode: PrintMode) -> String {
         match self {
-            Term::Abs(s, t1, t2) => colorize_string(format!("<bright-green><b>λ</>{}<bright-green>:</>{} {}", s, t1.print(), t2.print())),
-            _ => self.print_middle()
+            Term::Abs(s, t1, t2) => format!("{}{}{}{}{} {}", paint(mode, "<bright-green><b>", "λ"), s, paint(mode, "<bright-green>", ":"), t1.print_in(mode), paint(mode, "<bright-green>", "."), t2.print_in(mode)),
+            _ => self.print_middle(mode)
         }
     }
 
-    fn print_middle(&self) -> String {
+    fn print_middle(&self, mode: PrintMode) -> String {
         match self {
-            Term::App(t1, t2) => format!("{} {}", t1.print_middle(), t2.print_atomic()),
-            _ => self.print_atomic()
+            Term::App(t1, t2) => format!("{} {}", t1.print_middle(mode), t2.print_atomic(mode)),
+            _ => self.print_atomic(mode)
         }
     }
 
-    fn print_atomic(&self) -> String {
+    fn print_atomic(&self, mode: PrintMode) -> String {
         match self {
-            Term::Meta(s) => colorize_string(format!("<red><i>{}</>", s)),
+            Term::Meta(s) => paint(mode, "<red><i>", &meta_name(s)),
             Term::Var(s) => s.to_string(),
-            _ => format!("({})", self.print())
+            _ => format!("({})", self.print_in(mode))
         }
     }
 }
 
 impl Type {
     pub fn print(&self) -> String {
+        self.print_in(print_mode())
+    }
+
+    pub fn print_in(&self, mode: PrintMode) -> String {
         match self {
             Type::Arrow(t1, t2) => {
-                colorize_string(format!("<bright-blue><b>{} -> {}</>", t1.print_atomic(), t2.print()))
+                format!("{} {} {}", t1.print_atomic(mode), paint(mode, "<bright-blue><b>", "->"), t2.print_in(mode))
             }
-            _ => self.print_atomic()
+            _ => self.print_atomic(mode)
         }
     }
 
-    fn print_atomic(&self) -> String {
+    fn print_atomic(&self, mode: PrintMode) -> String {
         match self {
-            Type::Star => colorize_string(String::from("<bright-blue><b>*</>")),
-            _ => format!("({})", self.print())
+            Type::Star => paint(mode, "<bright-blue><b>", "*"),
+            _ => format!("({})", self.print_in(mode))
         }
     }
 }
 
 impl Constraint {
     pub fn print(&self) -> String {
-        colorize_string(format!("{} <yellow><b>=?</> {}", self.left.print(), self.right.print()))
+        self.print_in(print_mode())
+    }
+
+    pub fn print_in(&self, mode: PrintMode) -> String {
+        format!("{} {} {}", self.left.print_in(mode), paint(mode, "<yellow><b>", "=?"), self.right.print_in(mode))
     }
 }
 
 impl Problem {
     pub fn print(&self) -> String {
-        colorize_string(self.0.iter().map(|constraint| constraint.print()).collect::<Vec<_>>().join(" <bright-yellow><b>∧</> "))
+        self.print_in(print_mode())
+    }
+
+    pub fn print_in(&self, mode: PrintMode) -> String {
+        self.0.iter().map(|constraint| constraint.print_in(mode)).collect::<Vec<_>>().join(&format!(" {} ", paint(mode, "<bright-yellow><b>", "∧")))
     }
 }
 
 impl Substitution {
     pub fn print(&self) -> String {
-        colorize_string(format!("<red><i>{}</> <yellow>=></> {}", self.name, self.with.print()))
+        self.print_in(print_mode())
     }
-}
-
 
+    pub fn print_in(&self, mode: PrintMode) -> String {
+        format!("{} {} {}", paint(mode, "<red><i>", &meta_name(&self.name)), paint(mode, "<yellow>", "=>"), self.with.print_in(mode))
+    }
+}
 
+/// The fresh metavariables are numbered, and are printed as `?n` to tell them from variables.
+/// A printed `?n` parses back as the metavariable `n`.
+fn meta_name(name: &str) -> String {
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("?{}", name)
+    } else {
+        name.to_string()
+    }
+}
 
 impl Display for Term {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -95,6 +215,92 @@ impl Display for Substitution {
     }
 }
 
+impl TypeError {
+    pub fn print_in(&self, mode: PrintMode) -> String {
+        match self {
+            TypeError::UnknownConstant(s, constraint) =>
+                format!("The constant {} in {} is not declared", s, constraint.print_in(mode)),
+            TypeError::Mismatch(expected, found, constraint) =>
+                format!("The constraint {} is ill-typed: {} does not match {}", constraint.print_in(mode), expected.print_in(mode), found.print_in(mode)),
+            TypeError::InfiniteType(constraint) =>
+                format!("The constraint {} is ill-typed: a term would need an infinite type", constraint.print_in(mode)),
+        }
+    }
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.print_in(print_mode()))
+    }
+}
+
+impl std::error::Error for TypeError {}
+
+impl Clash {
+    pub fn print_in(&self, mode: PrintMode) -> String {
+        match self {
+            Clash::Occurs(name, constraint) =>
+                format!("{} occurs rigidly in the other side of {}", Term::Meta(name.clone()).print_in(mode), constraint.print_in(mode)),
+            Clash::Heads(left, right, constraint) =>
+                format!("the rigid heads {} and {} differ in {}", left.print_in(mode), right.print_in(mode), constraint.print_in(mode)),
+            Clash::Scope(name, variable, constraint) =>
+                format!("{} can not depend on {} in {}", Term::Meta(name.clone()).print_in(mode), Term::Var(variable.clone()).print_in(mode), constraint.print_in(mode)),
+        }
+    }
+}
+
+impl Display for Clash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.print_in(print_mode()))
+    }
+}
+
+impl NodeOutcome {
+    pub fn print_in(&self, mode: PrintMode) -> String {
+        match self {
+            NodeOutcome::Unexplored => "unexplored".to_string(),
+            NodeOutcome::CutOff => "cut off by the limits".to_string(),
+            NodeOutcome::Clash(clash) => format!("clash: {}", clash.print_in(mode)),
+            NodeOutcome::Solved(index) => format!("solution {}", index + 1),
+            NodeOutcome::Pattern => "pattern".to_string(),
+            NodeOutcome::Matched(constraint) => format!("matched {}", constraint.print_in(mode)),
+        }
+    }
+}
+
+impl Display for NodeOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.print_in(print_mode()))
+    }
+}
+
+impl Display for Step {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Step::Imitation => write!(f, "imitation"),
+            Step::Projection => write!(f, "projection"),
+            Step::Pattern => write!(f, "pattern"),
+        }
+    }
+}
+
+impl Trace {
+    fn print_node(&self, f: &mut Formatter<'_>, index: usize, indent: usize) -> std::fmt::Result {
+        let node = &self.0[index];
+
+        match &node.substitution {
+            Some((step, substitution)) => writeln!(f, "{}#{} {} {}: {}", "  ".repeat(indent), index, step, substitution, node.outcome)?,
+            None => writeln!(f, "{}#{} {}: {}", "  ".repeat(indent), index, node.problem, node.outcome)?,
+        }
+
+        for child in self.children(index) {
+            self.print_node(f, *child, indent + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
 struct PrintHelper(String);
 
 impl Debug for PrintHelper {
@@ -107,7 +313,13 @@ impl Display for Solution {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_list()
             .entries(self.0.iter().map(|a| PrintHelper(format!("{}", a))))
-            .finish()
+            .finish()?;
+
+        if !self.1.is_empty() {
+            write!(f, " with residual {}", Problem(self.1.clone()))?;
+        }
+
+        Ok(())
     }
 }
 
@@ -119,4 +331,143 @@ impl Display for SolutionSet {
         }
         writeln!(f, "]")
     }
-}
\ No newline at end of file
+}
+
+impl Display for Trace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        self.print_node(f, 0, 0)
+    }
+}
+
+impl Explanation {
+    pub fn print_in(&self, mode: PrintMode) -> String {
+        let mut printed = match self.failures.len() {
+            0 => "No branch of the search failed.\n".to_string(),
+            1 => "1 branch of the search failed:\n".to_string(),
+            n => format!("{} branches of the search failed:\n", n),
+        };
+
+        for (origins, clashes) in self.by_origin() {
+            printed += &format!("  from {}:\n", Problem(origins.to_vec()).print_in(mode));
+            for (clash, count) in clashes {
+                match count {
+                    1 => printed += &format!("    {}\n", clash.print_in(mode)),
+                    n => printed += &format!("    {} ({} branches)\n", clash.print_in(mode), n),
+                }
+            }
+        }
+
+        if self.outcome == SearchOutcome::Incomplete {
+            printed += "The search was cut off by the limits, so the unexplored branches may have solutions.\n";
+        }
+
+        printed
+    }
+}
+
+impl Display for Explanation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.print_in(print_mode()))
+    }
+}
+
+
+/// A small deterministic generator of random terms for the round-trip tests, based on a linear
+/// congruential generator.
+#[cfg(test)]
+struct Generator(u64);
+
+#[cfg(test)]
+impl Generator {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((self.0 >> 33) % bound as u64) as usize
+    }
+
+    fn pick(&mut self, names: &[&str]) -> String {
+        names[self.next(names.len())].to_string()
+    }
+
+    fn typ(&mut self, depth: usize) -> Type {
+        if depth == 0 || self.next(3) == 0 {
+            Type::Star
+        } else {
+            Type::Arrow(Box::new(self.typ(depth - 1)), Box::new(self.typ(depth - 1)))
+        }
+    }
+
+    fn term(&mut self, depth: usize) -> Term {
+        let choice = if depth == 0 { self.next(2) } else { self.next(4) };
+
+        match choice {
+            0 => Term::Meta(self.pick(&["F", "I", "PQ", "0", "17"])),
+            1 => Term::Var(self.pick(&["x", "y_1", "u32", "option", "3", "42", "const", "meta", "problem", "expect"])),
+            2 => Term::Abs(self.pick(&["x", "z", "5", "const"]), self.typ(2), Box::new(self.term(depth - 1))),
+            _ => Term::App(Box::new(self.term(depth - 1)), Box::new(self.term(depth - 1))),
+        }
+    }
+
+    fn constraint(&mut self, depth: usize) -> Constraint {
+        Constraint::new(self.term(depth), self.term(depth))
+    }
+
+    fn problem(&mut self, depth: usize) -> Problem {
+        let count = self.next(4);
+        Problem((0..count).map(|_| self.constraint(depth)).collect())
+    }
+}
+
+#[test]
+fn test_round_trip_types() {
+    // Arrange
+    let mut generator = Generator(1);
+
+    for _ in 0..200 {
+        let typ = generator.typ(4);
+
+        // Act
+        let actual = crate::parse::parse_type(&typ.print_in(PrintMode::Plain));
+
+        // Assert
+        assert_eq!(actual, Ok(typ));
+    }
+}
+
+#[test]
+fn test_round_trip_terms() {
+    // Arrange
+    let mut generator = Generator(2);
+
+    for _ in 0..200 {
+        let term = generator.term(5);
+
+        // Act
+        let actual = crate::parse::parse_term(&term.print_in(PrintMode::Plain));
+
+        // Assert
+        assert_eq!(actual, Ok(term));
+    }
+}
+
+#[test]
+fn test_round_trip_constraints_and_problems() {
+    // Arrange
+    let mut generator = Generator(3);
+
+    for _ in 0..100 {
+        let constraint = generator.constraint(4);
+        let problem = generator.problem(3);
+
+        // Act
+        let actual_constraint = crate::parse::parse_constraint(&constraint.print_in(PrintMode::Plain));
+        let actual_problem = crate::parse::parse_problem(&problem.print_in(PrintMode::Plain));
+
+        // Assert
+        assert_eq!(actual_constraint, Ok(constraint));
+        assert_eq!(actual_problem, Ok(problem));
+    }
+}
@@ -1,7 +1,31 @@
+use std::cmp::Reverse;
 use crate::datatype::{Solution, SolutionSet};
 
+/// A priority filters a set of solutions down to the ones it prefers.
+pub type Priority = fn(SolutionSet) -> SolutionSet;
+
+/// The priorities by name, in the order they are applied by [`get_solution_from_solution_set`].
+/// The ones after `most_general` are heuristics, which break the ties between the most general
+/// solutions.
+pub const PRIORITIES: &[(&str, Priority)] = &[
+    ("most_general", most_general),
+    ("existence", existence),
+    ("generality", generality),
+    ("exhaustiveness", exhaustiveness),
+    ("ordering", ordering),
+    ("simplicity", simplicity),
+];
+
+/// Look up a priority by its name.
+pub fn priority_by_name(name: &str) -> Option<Priority> {
+    PRIORITIES.iter().find(|(priority_name, _)| *priority_name == name).map(|(_, priority)| *priority)
+}
+
+/// Filter the solutions by all the priorities, after removing the duplicates. Returns the
+/// solution if exactly one remains, and otherwise the remaining solutions.
 pub fn get_solution_from_solution_set(solutions: SolutionSet) -> Result<Solution, SolutionSet> {
-    let existence_filtered = existence(solutions);
+    let most_general_filtered = most_general(solutions.dedup());
+    let existence_filtered = existence(most_general_filtered);
     let generality_filtered = generality(existence_filtered);
     let exhaustiveness_filtered = exhaustiveness(generality_filtered);
     let ordering_filtered = ordering(exhaustiveness_filtered);
@@ -14,16 +38,31 @@ pub fn get_solution_from_solution_set(solutions: SolutionSet) -> Result<Solution
     }
 }
 
-pub fn get_solution_from_solution_set_by_priorities(solutions: SolutionSet, fs: &[fn(SolutionSet) -> SolutionSet]) -> SolutionSet {
-    let mut sol = solutions;
+/// Filter the solutions by the priorities, in order, after removing the duplicates.
+pub fn get_solution_from_solution_set_by_priorities(solutions: SolutionSet, fs: &[Priority]) -> SolutionSet {
+    let mut sol = solutions.dedup();
     for &f in fs {
         sol = f(sol);
     }
     sol
 }
 
+/// Keep the solutions that are not instances of another solution. Of solutions that are
+/// instances of each other, only the first is kept.
+pub fn most_general(solutions: SolutionSet) -> SolutionSet {
+    let kept = solutions.0.iter()
+        .enumerate()
+        .filter(|(index, solution)| !solutions.0.iter().enumerate().any(|(other_index, other)| {
+            other_index != *index && solution.is_instance_of(other) && (other_index < *index || !other.is_instance_of(solution))
+        }))
+        .map(|(_, solution)| solution.clone())
+        .collect();
+
+    SolutionSet(kept)
+}
+
 pub fn existence(mut solutions: SolutionSet) -> SolutionSet {
-    solutions.0.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+    solutions.0.sort_by_key(|a| Reverse(a.0.len()));
     let mut new_list = Vec::new();
     if let Some(first) = solutions.0.first() {
         let max = first.0.len();
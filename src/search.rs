@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use crate::datatype::{Clash, Context, fresh_var_mark, Problem, Provenance, Solution, Substitution, Type};
+use crate::explain::{Explanation, Failure};
+use crate::r#match::match_;
+use crate::occurs::occurs_check;
+use crate::pattern::{pattern_unify, PatternResult};
+use crate::simpl::simpl;
+use crate::typing::infer_substitution;
+use crate::substs::{normalize, normalize_problem, problem_substitution};
+use crate::trace::{NodeOutcome, Step, Trace, TraceNode};
+
+/// Bounds on the search. Higher-order unification is undecidable, so without limits the search
+/// is not guaranteed to terminate. A limit of `None` means unbounded.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SearchLimits {
+    /// The maximum number of substitutions from `match_` on a single branch. The deterministic
+    /// steps of the pattern fragment do not count.
+    pub max_depth: Option<usize>,
+    /// The maximum number of nodes explored in total.
+    pub max_nodes: Option<usize>,
+    /// The maximum size of either side of a constraint. Branches with larger terms are pruned.
+    pub max_term_size: Option<usize>,
+}
+
+/// Whether the search explored the whole tree, or had parts of it cut off by the limits.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SearchOutcome {
+    Complete,
+    Incomplete,
+}
+
+/// A node in the search tree: the substitutions chosen on the way down and the problem
+/// that remains after applying them.
+#[derive(Clone, PartialEq, Debug)]
+struct Node {
+    /// The index of the node in the trace, if the search is traced.
+    id: usize,
+    depth: usize,
+    substitutions: Vec<Substitution>,
+    problem: Problem,
+    /// The types of the fresh metavariables introduced on the way down, which extend the typing
+    /// context of the search in this branch only.
+    typing: HashMap<String, Type>,
+}
+
+/// A lazy depth-first walk of the match tree. Each call to `next` explores the tree until
+/// the next solution is found, so the search can be stopped at any point.
+///
+/// Flex-rigid constraints are selected before flex-flex ones, and a branch where only flex-flex
+/// constraints remain is a solution with those constraints as residuals.
+///
+/// The solutions are yielded in the order they are found. The substitutions for the fresh
+/// metavariables of the search are composed into the others, but the name map is not applied.
+#[derive(Clone, Debug)]
+pub struct Search {
+    context: Context,
+    /// The original problem, after normalization.
+    problem: Problem,
+    stack: Vec<Node>,
+    limits: SearchLimits,
+    explored: usize,
+    outcome: SearchOutcome,
+    clashes: Vec<Clash>,
+    trace: Option<Trace>,
+    found: usize,
+    /// The first fresh variable of the search. Numeric names below it come from the problem.
+    fresh_from: u32,
+}
+
+impl Search {
+    /// Create a search for the problem. The constraints are normalized to beta-normal, eta-long
+    /// form first, so they can be written with arbitrary lambda terms. Each constraint gets its
+    /// index in the problem as its provenance.
+    pub fn new(context: Context, problem: Problem) -> Search {
+        let mut problem = normalize_problem(problem, &context.typing_context);
+        for (index, constraint) in problem.0.iter_mut().enumerate() {
+            constraint.provenance = Provenance::of(index);
+        }
+
+        let root = Node {
+            id: 0,
+            depth: 0,
+            substitutions: context.substitutions.clone(),
+            problem: problem.clone(),
+            typing: HashMap::new(),
+        };
+
+        Search {
+            context,
+            problem,
+            stack: vec![root],
+            limits: SearchLimits::default(),
+            explored: 0,
+            outcome: SearchOutcome::Complete,
+            clashes: vec![],
+            trace: None,
+            found: 0,
+            fresh_from: fresh_var_mark(),
+        }
+    }
+
+    pub fn with_limits(mut self, limits: SearchLimits) -> Search {
+        self.limits = limits;
+        self
+    }
+
+    /// Record the search tree as it is explored. Must be called before the search starts.
+    pub fn with_trace(mut self) -> Search {
+        let root = self.stack.first().map(|root| TraceNode {
+            parent: None,
+            children: vec![],
+            depth: 0,
+            substitution: None,
+            problem: root.problem.clone(),
+            simplified: None,
+            outcome: NodeOutcome::Unexplored,
+        });
+
+        self.trace = Some(Trace(root.into_iter().collect()));
+        self
+    }
+
+    /// The search tree explored so far, if the search is traced.
+    pub fn trace(&self) -> Option<&Trace> {
+        self.trace.as_ref()
+    }
+
+    /// The number of nodes explored so far.
+    pub fn explored(&self) -> usize {
+        self.explored
+    }
+
+    /// Whether any part of the tree was cut off by the limits so far. This is only final once
+    /// the iterator has returned `None`.
+    pub fn outcome(&self) -> SearchOutcome {
+        self.outcome
+    }
+
+    /// The reasons for the failed branches found so far.
+    pub fn clashes(&self) -> &[Clash] {
+        &self.clashes
+    }
+
+    /// The failed branches found so far, with the constraints of the original problem they come
+    /// from.
+    pub fn failures(&self) -> Vec<Failure> {
+        self.clashes.iter()
+            .map(|clash| Failure {
+                clash: clash.clone(),
+                origins: clash.constraint().provenance.0.iter().map(|index| self.problem.0[*index].clone()).collect(),
+            })
+            .collect()
+    }
+
+    /// Why the branches of the search failed so far. This is only final once the iterator has
+    /// returned `None`.
+    pub fn explanation(&self) -> Explanation {
+        Explanation {
+            failures: self.failures(),
+            outcome: self.outcome,
+        }
+    }
+
+    fn context_for(&self, node: &Node) -> Context {
+        Context {
+            typing_context: self.typing_context_for(node),
+            substitutions: node.substitutions.clone(),
+            solutions: self.context.solutions.clone(),
+            name_map: self.context.name_map.clone(),
+        }
+    }
+
+    fn typing_context_for(&self, node: &Node) -> HashMap<String, Type> {
+        self.typing_context_with(&node.typing)
+    }
+
+    fn typing_context_with(&self, typing: &HashMap<String, Type>) -> HashMap<String, Type> {
+        let mut typing_context = self.context.typing_context.clone();
+        typing_context.extend(typing.iter().map(|(name, typ)| (name.clone(), typ.clone())));
+        typing_context
+    }
+
+    fn record(&mut self, node: &Node, outcome: NodeOutcome) {
+        if let Some(trace) = &mut self.trace {
+            trace.0[node.id].outcome = outcome;
+        }
+    }
+
+    fn fail(&mut self, node: &Node, clash: Clash) {
+        self.record(node, NodeOutcome::Clash(clash.clone()));
+        self.clashes.push(clash);
+    }
+
+    fn record_simplified(&mut self, node: &Node, simplified: &Problem) {
+        if let Some(trace) = &mut self.trace {
+            trace.0[node.id].simplified = Some(simplified.clone());
+        }
+    }
+
+    fn push(&mut self, parent: &Node, problem: &Problem, step: Step, mut substitution: Substitution, depth: usize) {
+        // The fresh metavariables of the substitution get their types from the metavariable
+        // it substitutes, in the branch below the parent.
+        let mut typing = parent.typing.clone();
+        typing.extend(infer_substitution(&substitution, &self.typing_context_for(parent)));
+
+        // The substitution and the problem are normalized again with those types, such that the
+        // constraints stay in beta-normal, eta-long form.
+        let typing_context = self.typing_context_with(&typing);
+        substitution.with = normalize(substitution.with, &typing_context);
+        let new_problem = normalize_problem(problem_substitution(problem.clone(), substitution.clone()), &typing_context);
+
+        let id = match &mut self.trace {
+            Some(trace) => {
+                // The children are pushed on the stack in reverse, so the last one is explored first.
+                let id = trace.0.len();
+                trace.0[parent.id].children.insert(0, id);
+                trace.0.push(TraceNode {
+                    parent: Some(parent.id),
+                    children: vec![],
+                    depth,
+                    substitution: Some((step, substitution.clone())),
+                    problem: new_problem.clone(),
+                    simplified: None,
+                    outcome: NodeOutcome::Unexplored,
+                });
+                id
+            }
+            None => 0,
+        };
+
+        let mut substitutions = parent.substitutions.clone();
+        substitutions.push(substitution);
+
+        self.stack.push(Node {
+            id,
+            depth,
+            substitutions,
+            problem: new_problem,
+            typing,
+        });
+    }
+
+    /// Whether the metavariable was introduced by the search rather than written in the problem.
+    fn is_fresh(&self, name: &str) -> bool {
+        u32::from_str(name).is_ok_and(|number| number >= self.fresh_from)
+    }
+
+    fn exceeds_limits(&self, node: &Node) -> bool {
+        let too_deep = self.limits.max_depth.is_some_and(|max| node.depth > max);
+        let too_large = self.limits.max_term_size.is_some_and(|max| {
+            node.problem.0.iter().any(|constraint| constraint.left.size() > max || constraint.right.size() > max)
+        });
+
+        too_deep || too_large
+    }
+}
+
+impl Iterator for Search {
+    type Item = Solution;
+
+    fn next(&mut self) -> Option<Solution> {
+        while let Some(node) = self.stack.pop() {
+            if self.limits.max_nodes.is_some_and(|max| self.explored >= max) {
+                self.stack.clear();
+                self.outcome = SearchOutcome::Incomplete;
+                return None;
+            }
+
+            if self.exceeds_limits(&node) {
+                self.outcome = SearchOutcome::Incomplete;
+                self.record(&node, NodeOutcome::CutOff);
+                continue;
+            }
+
+            self.explored += 1;
+
+            let context = self.context_for(&node);
+
+            let p_simpl = match simpl(context.clone(), node.problem.clone()) {
+                Ok(p_simpl) => {
+                    self.record_simplified(&node, &p_simpl);
+                    p_simpl
+                }
+                Err(clash) => {
+                    self.fail(&node, *clash);
+                    continue;
+                }
+            };
+
+            // Only flex-flex constraints remain, which are returned as residuals.
+            let constraint = match p_simpl.0.iter().find(|constraint| !constraint.is_flex_flex()) {
+                Some(constraint) => constraint.clone(),
+                None => {
+                    self.record(&node, NodeOutcome::Solved(self.found));
+                    self.found += 1;
+                    return Some(Solution(node.substitutions, p_simpl.0).compose(|name| self.is_fresh(name)));
+                }
+            };
+
+            if let Some(clash) = p_simpl.0.iter().find_map(occurs_check) {
+                self.fail(&node, clash);
+                continue;
+            }
+
+            // Constraints in the pattern fragment are solved without branching.
+            let pattern = p_simpl.0.iter()
+                .map(|constraint| (pattern_unify(constraint, &context.typing_context), constraint))
+                .find(|(result, _)| *result != PatternResult::NotPattern);
+
+            match pattern {
+                Some((PatternResult::Clash(clash), _)) => {
+                    self.fail(&node, clash);
+                    continue;
+                }
+                Some((PatternResult::Step(mut substitution), constraint)) => {
+                    substitution.provenance = constraint.provenance.clone();
+                    self.record(&node, NodeOutcome::Pattern);
+                    self.push(&node, &p_simpl, Step::Pattern, substitution, node.depth);
+                    continue;
+                }
+                _ => {}
+            }
+
+            self.record(&node, NodeOutcome::Matched(constraint.clone()));
+            let provenance = constraint.provenance.clone();
+            let substitution_set = match_(context, constraint);
+
+            // Push in reverse, such that the first substitution, the imitation, is explored first.
+            for (step, mut substitution) in substitution_set.into_iter().rev() {
+                substitution.provenance = provenance.clone();
+                self.push(&node, &p_simpl, step, substitution, node.depth + 1);
+            }
+        }
+
+        None
+    }
+}
+
+
+#[test]
+fn test_branch_types_stay_in_their_branch() {
+    // Arrange
+    let mut unifier = crate::Unifier::default();
+    unifier.declare("u32", Type::Star);
+    unifier.declare("option", crate::parse::parse_type("* -> *").unwrap());
+    unifier.declare("I", crate::parse::parse_type("* -> *").unwrap());
+    let context = unifier.context();
+    let mut search = Search::new(context.clone(), crate::parse::parse_problem("I u32 =? option u32").unwrap());
+
+    // Act
+    let solutions = search.by_ref().count();
+
+    // Assert
+    assert_eq!(solutions, 2);
+    assert_eq!(search.context.typing_context, context.typing_context);
+}
@@ -1,10 +1,16 @@
-use crate::datatype::{Constraint, Context, Problem};
+use crate::datatype::{Clash, Constraint, Context, Problem};
 
-pub fn simpl(context: Context, problem: Problem) -> Option<Problem> {
+/// Decompose the rigid-rigid constraints of the problem. The constraints a constraint decomposes
+/// into inherit its provenance. Fails with the first clash of rigid heads.
+pub fn simpl(context: Context, problem: Problem) -> Result<Problem, Box<Clash>> {
     let mut queue = problem.0.clone();
     let mut simplified = vec![];
 
     while let Some(constraint) = queue.pop() {
+        if constraint.left.alpha_equivalent(&constraint.right) {
+            continue;
+        }
+
         if constraint.is_rigid_rigid() {
             queue.append(&mut simplify_constraint(context.clone(), constraint)?);
         } else {
@@ -12,10 +18,10 @@ pub fn simpl(context: Context, problem: Problem) -> Option<Problem> {
         }
     }
 
-    Some(Problem(simplified))
+    Ok(Problem(simplified))
 }
 
-fn simplify_constraint(context: Context, constraint: Constraint) -> Option<Vec<Constraint>> {
+fn simplify_constraint(context: Context, constraint: Constraint) -> Result<Vec<Constraint>, Box<Clash>> {
     let (l_lambda, l_head, l_tail) = constraint.left.split();
     let (r_lambda, r_head, r_tail) = constraint.right.split();
 
@@ -25,15 +31,16 @@ fn simplify_constraint(context: Context, constraint: Constraint) -> Option<Vec<C
         }).unwrap_or(false) {
         let mut builder = vec![];
 
-        for (l_elem, r_elem) in l_tail.into_iter().zip(r_tail.into_iter()) {
+        for (l_elem, r_elem) in l_tail.into_iter().zip(r_tail) {
             builder.push(Constraint {
                 left: l_elem.combine(l_lambda.clone()),
-                right: r_elem.combine(r_lambda.clone())
+                right: r_elem.combine(r_lambda.clone()),
+                provenance: constraint.provenance.clone(),
             })
         }
 
-        Some(builder)
+        Ok(builder)
     } else {
-        None
+        Err(Box::new(Clash::Heads(l_head, r_head, constraint)))
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,89 @@
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use crate::datatype::{Context, Problem, Solution, SolutionSet};
+use crate::parse::{parse_problem_file, ParseError};
+use crate::substs::normalize;
+use crate::unifier::Unifier;
+
+/// A named problem of a problem file, with the typing context given by its declarations and,
+/// optionally, the solutions it is expected to have.
+///
+/// A problem file starts with the declarations shared by all its problems, followed by the
+/// problems themselves. Line comments start with `//`.
+///
+/// ```text
+/// const u32 : *;
+/// const option : * -> *;
+///
+/// problem example_1 {
+///     meta I : * -> *;
+///     I u32 =? option u32;
+///     expect I => λx:*. option x;
+///     expect I => λx:*. option u32;
+/// }
+/// ```
+///
+/// A solution with several substitutions separates them by commas, and `expect ∅;` states that
+/// the problem has no solutions.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ProblemSpec {
+    pub name: String,
+    pub context: Context,
+    pub problem: Problem,
+    pub expected: Option<SolutionSet>,
+}
+
+/// The reason a problem file could not be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, Box<ParseError>),
+}
+
+/// Read and parse the problem file at the path.
+pub fn load_problems(path: impl AsRef<Path>) -> Result<Vec<ProblemSpec>, LoadError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path).map_err(|error| LoadError::Io(path.to_path_buf(), error))?;
+    parse_problem_file(&source).map_err(|error| LoadError::Parse(path.to_path_buf(), Box::new(error)))
+}
+
+impl ProblemSpec {
+    /// A unifier with the typing context of the problem.
+    pub fn unifier(&self) -> Unifier {
+        Unifier::new(self.context.typing_context.clone())
+    }
+
+    /// Whether the solutions are the expected ones, up to the order of the solutions and the
+    /// names of the bound variables. Holds for any solutions if none are expected.
+    pub fn is_expected(&self, solutions: &SolutionSet) -> bool {
+        let expected = match &self.expected {
+            Some(expected) => expected,
+            None => return true,
+        };
+
+        expected.0.len() == solutions.0.len()
+            && expected.0.iter().all(|expected| solutions.0.iter().any(|solution| self.same_solution(expected, solution)))
+    }
+
+    fn same_solution(&self, expected: &Solution, solution: &Solution) -> bool {
+        let typing_context = &self.context.typing_context;
+
+        expected.0.len() == solution.0.len()
+            && expected.0.iter().all(|expected| solution.0.iter().any(|substitution| {
+                substitution.name == expected.name
+                    && normalize(substitution.with.clone(), typing_context)
+                        .alpha_equivalent(&normalize(expected.with.clone(), typing_context))
+            }))
+    }
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Io(path, error) => write!(f, "Could not read {}: {}", path.display(), error),
+            LoadError::Parse(path, error) => write!(f, "Could not parse {}:\n{}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
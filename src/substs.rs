@@ -1,8 +1,16 @@
-use crate::datatype::{Constraint, Problem, Substitution, Term};
+use std::collections::HashMap;
+use crate::datatype::{Constraint, generate_fresh_var, Problem, Substitution, Term, Type};
 
+/// Substitute `sub.with` for the free occurrences of the variable `sub.name` in the term. Binders
+/// that would capture a free variable of `sub.with` are renamed to a fresh variable first.
 pub fn term_substitution(term: Term, sub: Substitution) -> Term {
     match term {
-        Term::Meta(s) | Term::Var(s) if s == sub.name => sub.with,
+        Term::Var(s) if s == sub.name => sub.with,
+        Term::Abs(s, typ, t1) if s == sub.name => Term::Abs(s, typ, t1),
+        Term::Abs(s, typ, t1) if sub.with.free_vars().contains(&s) => {
+            let (fresh, renamed) = rename_binder(s, *t1);
+            Term::Abs(fresh, typ, Box::new(term_substitution(renamed, sub)))
+        }
         Term::Abs(s, typ, t1) =>
             Term::Abs(s, typ, Box::new(term_substitution(*t1, sub))),
         Term::App(t1, t2) => {
@@ -16,12 +24,41 @@ pub fn term_substitution(term: Term, sub: Substitution) -> Term {
     }
 }
 
+/// Substitute `sub.with` for the occurrences of the metavariable `sub.name` in the term.
+/// Metavariables are never bound, so no binder shadows them, but binders that would capture a
+/// free variable of `sub.with` are renamed to a fresh variable first.
+pub fn meta_substitution(term: Term, sub: Substitution) -> Term {
+    match term {
+        Term::Meta(s) if s == sub.name => sub.with,
+        Term::Abs(s, typ, t1) if sub.with.free_vars().contains(&s) => {
+            let (fresh, renamed) = rename_binder(s, *t1);
+            Term::Abs(fresh, typ, Box::new(meta_substitution(renamed, sub)))
+        }
+        Term::Abs(s, typ, t1) =>
+            Term::Abs(s, typ, Box::new(meta_substitution(*t1, sub))),
+        Term::App(t1, t2) => {
+            let app_term = Term::App(
+                Box::new(meta_substitution(*t1, sub.clone())),
+                Box::new(meta_substitution(*t2, sub))
+            );
+            beta_reduce(app_term)
+        }
+        _ => term
+    }
+}
+
+fn rename_binder(s: String, body: Term) -> (String, Term) {
+    let fresh = generate_fresh_var();
+    let renamed = term_substitution(body, Substitution::new(s, Term::Var(fresh.clone())));
+    (fresh, renamed)
+}
+
 pub fn beta_reduce(term: Term) -> Term {
     match term {
         Term::App(t1, t2) => {
             match *t1 {
                 Term::Abs(s, _, t11) =>
-                    term_substitution(*t11, Substitution { name: s, with: *t2 }),
+                    term_substitution(*t11, Substitution::new(s, *t2)),
                 _ => Term::App(t1, t2)
             }
         }
@@ -29,14 +66,123 @@ pub fn beta_reduce(term: Term) -> Term {
     }
 }
 
+/// Normalize the term to beta-normal, eta-long form. The eta-expansion is driven by the expected
+/// type of each position: the argument types of the constants and metavariables in the typing
+/// context and the types of the binders. Positions of unknown type, such as the term itself,
+/// are not expanded.
+pub fn normalize(term: Term, typing_context: &HashMap<String, Type>) -> Term {
+    eta_expand(beta_normalize(term), None, typing_context)
+}
+
+fn beta_normalize(term: Term) -> Term {
+    match term {
+        Term::Abs(s, typ, t1) => Term::Abs(s, typ, Box::new(beta_normalize(*t1))),
+        Term::App(t1, t2) => {
+            match beta_normalize(*t1) {
+                Term::Abs(s, typ, t11) =>
+                    beta_normalize(beta_reduce(Term::App(Box::new(Term::Abs(s, typ, t11)), t2))),
+                t1 => Term::App(Box::new(t1), Box::new(beta_normalize(*t2)))
+            }
+        }
+        _ => term
+    }
+}
+
+fn eta_expand(term: Term, expected: Option<&Type>, typing_context: &HashMap<String, Type>) -> Term {
+    let mut new_context = typing_context.clone();
+
+    let mut binders = vec![];
+    let mut body = term;
+    while let Term::Abs(s, typ, inner) = body {
+        new_context.insert(s.clone(), typ.clone());
+        binders.push((s, typ));
+        body = *inner;
+    }
+
+    // The arguments of the expected type that are not bound by an abstraction yet.
+    let missing = expected.map(Type::arguments).unwrap_or_default().into_iter()
+        .skip(binders.len())
+        .map(|typ| (generate_fresh_var(), typ))
+        .collect::<Vec<_>>();
+
+    for (fresh_var, typ) in &missing {
+        new_context.insert(fresh_var.clone(), typ.clone());
+    }
+
+    let (_, head, arguments) = body.split();
+    let argument_types = new_context.get(&head.get_name()).map(Type::arguments).unwrap_or_default();
+
+    let arguments = arguments.into_iter()
+        .chain(missing.iter().map(|(fresh_var, _)| Term::Var(fresh_var.clone())));
+
+    let mut builder = head;
+    for (index, argument) in arguments.enumerate() {
+        let argument = eta_expand(argument, argument_types.get(index), &new_context);
+        builder = Term::App(Box::new(builder), Box::new(argument));
+    }
+
+    for (s, typ) in binders.into_iter().chain(missing).rev() {
+        builder = Term::Abs(s, typ, Box::new(builder));
+    }
+
+    builder
+}
 
+/// Eta-expand the side of the constraint with fewer abstractions, such that both sides have the
+/// same number of abstractions.
+fn align_binders(left: Term, right: Term, typing_context: &HashMap<String, Type>) -> (Term, Term) {
+    let l_binders = binder_types(&left);
+    let r_binders = binder_types(&right);
+
+    if l_binders.len() < r_binders.len() {
+        (eta_expand(left, Some(&function_type(r_binders)), typing_context), right)
+    } else if r_binders.len() < l_binders.len() {
+        (left, eta_expand(right, Some(&function_type(l_binders)), typing_context))
+    } else {
+        (left, right)
+    }
+}
+
+fn binder_types(term: &Term) -> Vec<Type> {
+    let mut current = term;
+    let mut types = vec![];
+    while let Term::Abs(_, typ, inner) = current {
+        types.push(typ.clone());
+        current = inner;
+    }
+    types
+}
+
+fn function_type(arguments: Vec<Type>) -> Type {
+    arguments.into_iter().rev().fold(Type::Star, |result, argument| Type::Arrow(Box::new(argument), Box::new(result)))
+}
+
+/// Apply the substitution to both sides of the constraint. If the substituted metavariable
+/// occurs in it, the constraint now also depends on the provenance of the substitution.
 pub fn constraint_substitution(constraint: Constraint, sub: Substitution) -> Constraint {
+    let mut provenance = constraint.provenance;
+    if constraint.left.contains_meta(&sub.name) || constraint.right.contains_meta(&sub.name) {
+        provenance.extend(&sub.provenance);
+    }
+
     Constraint {
-        left: term_substitution(constraint.left, sub.clone()),
-        right: term_substitution(constraint.right, sub)
+        left: meta_substitution(constraint.left, sub.clone()),
+        right: meta_substitution(constraint.right, sub),
+        provenance,
     }
 }
 
+pub fn normalize_problem(problem: Problem, typing_context: &HashMap<String, Type>) -> Problem {
+    Problem(problem.0.into_iter()
+        .map(|constraint| {
+            let left = normalize(constraint.left, typing_context);
+            let right = normalize(constraint.right, typing_context);
+            let (left, right) = align_binders(left, right, typing_context);
+            Constraint { left, right, provenance: constraint.provenance }
+        })
+        .collect())
+}
+
 pub fn problem_substitution(problem: Problem, sub: Substitution) -> Problem {
     Problem(problem.0.into_iter()
         .map(|constraint| constraint_substitution(constraint, sub.clone()))
@@ -44,3 +190,81 @@ pub fn problem_substitution(problem: Problem, sub: Substitution) -> Problem {
 }
 
 
+#[test]
+fn test_substitution_avoids_capture() {
+    // Arrange
+    let input = crate::parse::parse_term("(λx:*. λy:*. x) y").unwrap();
+
+    // Act
+    let actual = beta_reduce(input);
+
+    // Assert
+    match actual {
+        Term::Abs(s, _, body) => {
+            assert_ne!(s, "y");
+            assert_eq!(*body, Term::Var("y".to_string()));
+        }
+        _ => panic!("Expected an abstraction, got {:?}", actual)
+    }
+}
+
+#[test]
+fn test_normalize() {
+    // Arrange
+    let input = crate::parse::parse_term("(λf:* -> * -> *. apply f) result").unwrap();
+    let typing_context = HashMap::from([
+        ("result".to_string(), crate::parse::parse_type("* -> * -> *").unwrap()),
+        ("apply".to_string(), crate::parse::parse_type("(* -> * -> *) -> *").unwrap()),
+    ]);
+    let expected = crate::parse::parse_term("apply (λx:*. λy:*. result x y)").unwrap();
+
+    // Act
+    let actual = normalize(input, &typing_context);
+
+    // Assert
+    assert!(actual.alpha_equivalent(&expected), "{:?}", actual);
+}
+
+#[test]
+fn test_substitution_stops_at_shadowing_binder() {
+    // Arrange
+    let input = crate::parse::parse_term("λx:*. x").unwrap();
+    let sub = Substitution::new("x", Term::Var("u32".to_string()));
+
+    // Act
+    let actual = term_substitution(input.clone(), sub);
+
+    // Assert
+    assert_eq!(actual, input);
+}
+
+#[test]
+fn test_problem_substitution_keeps_provenance() {
+    // Arrange
+    let mut problem = crate::parse::parse_problem("I u32 =? option u32 ∧ J u32 =? u32").unwrap();
+    problem.0[0].provenance = crate::datatype::Provenance::of(0);
+    problem.0[1].provenance = crate::datatype::Provenance::of(1);
+
+    let mut sub = Substitution::new("I", crate::parse::parse_term("λx:*. option x").unwrap());
+    sub.provenance = crate::datatype::Provenance::of(2);
+
+    // Act
+    let actual = problem_substitution(problem, sub);
+
+    // Assert
+    assert_eq!(actual.0[0].provenance.0.iter().copied().collect::<Vec<_>>(), vec![0, 2]);
+    assert_eq!(actual.0[1].provenance, crate::datatype::Provenance::of(1));
+}
+
+#[test]
+fn test_meta_substitution_passes_binders() {
+    // Arrange
+    let input = Term::Abs("5".to_string(), Type::Star, Box::new(Term::Meta("5".to_string())));
+    let sub = Substitution::new("5", Term::Var("u32".to_string()));
+
+    // Act
+    let actual = meta_substitution(input, sub);
+
+    // Assert
+    assert_eq!(actual, Term::Abs("5".to_string(), Type::Star, Box::new(Term::Var("u32".to_string()))));
+}
@@ -0,0 +1,250 @@
+use std::collections::{HashMap, HashSet};
+use crate::datatype::{Constraint, generate_fresh_var, Solution, Substitution, Term, Type};
+use crate::substs::{meta_substitution, term_substitution};
+
+impl Solution {
+    /// Whether the solution is an instance of the other, that is whether there is a substitution
+    /// θ such that composing the other solution with θ gives this one on the original
+    /// metavariables. The original metavariables are the ones either solution substitutes, and a
+    /// metavariable that a solution does not substitute is left as it is.
+    ///
+    /// θ instantiates the metavariables of the terms of the other solution, which occur in its
+    /// residuals. Applying θ to each of those residuals must either solve it or give one of the
+    /// residuals of this solution. The metavariables may only be applied to distinct bound
+    /// variables, as in a pattern, where θ is unique. Otherwise the solutions are only related if
+    /// they are alpha-equivalent.
+    pub fn is_instance_of(&self, other: &Solution) -> bool {
+        let mut originals: Vec<&str> = vec![];
+        for substitution in self.0.iter().chain(&other.0) {
+            if !originals.contains(&substitution.name.as_str()) {
+                originals.push(&substitution.name);
+            }
+        }
+
+        let binding = |solution: &Solution, name: &str| solution.0.iter()
+            .find(|substitution| substitution.name == name)
+            .map(|substitution| substitution.with.clone())
+            .unwrap_or(Term::Meta(name.to_string()));
+
+        let mut flexible = HashSet::new();
+        for name in &originals {
+            metas(&binding(other, name), &mut flexible);
+        }
+        for constraint in &other.1 {
+            metas(&constraint.left, &mut flexible);
+            metas(&constraint.right, &mut flexible);
+        }
+
+        let mut matcher = Matcher { flexible, theta: HashMap::new() };
+        let matched = originals.iter().all(|name| {
+            matcher.match_term(&binding(other, name), &binding(self, name), &mut vec![])
+        });
+
+        matched && other.1.iter().all(|constraint| {
+            let instance = matcher.apply(constraint);
+            instance.left.alpha_equivalent(&instance.right) || self.1.iter().any(|residual| {
+                (residual.left.alpha_equivalent(&instance.left) && residual.right.alpha_equivalent(&instance.right)) ||
+                    (residual.left.alpha_equivalent(&instance.right) && residual.right.alpha_equivalent(&instance.left))
+            })
+        })
+    }
+}
+
+fn metas(term: &Term, found: &mut HashSet<String>) {
+    match term {
+        Term::Meta(name) => {
+            found.insert(name.clone());
+        }
+        Term::Var(_) => {}
+        Term::Abs(_, _, inner) => metas(inner, found),
+        Term::App(t1, t2) => {
+            metas(t1, found);
+            metas(t2, found);
+        }
+    }
+}
+
+/// The head of an application and its arguments.
+fn spine(term: &Term) -> (&Term, Vec<&Term>) {
+    let mut current = term;
+    let mut arguments = vec![];
+    while let Term::App(t1, t2) = current {
+        arguments.push(&**t2);
+        current = t1;
+    }
+    arguments.reverse();
+    (current, arguments)
+}
+
+/// Higher-order pattern matching of the terms of a general solution against the terms of an
+/// instance.
+struct Matcher {
+    /// The metavariables of the general solution, which θ can instantiate.
+    flexible: HashSet<String>,
+    theta: HashMap<String, Term>,
+}
+
+/// The binders the matched terms are under: the name in the general term, the name in the
+/// instance and the type.
+type Binders = Vec<(String, String, Type)>;
+
+impl Matcher {
+    fn match_term(&mut self, general: &Term, instance: &Term, binders: &mut Binders) -> bool {
+        if let (Term::Abs(x, x_type, general_inner), Term::Abs(y, y_type, instance_inner)) = (general, instance) {
+            if x_type != y_type {
+                return false;
+            }
+
+            binders.push((x.clone(), y.clone(), x_type.clone()));
+            let matched = self.match_term(general_inner, instance_inner, binders);
+            binders.pop();
+            return matched;
+        }
+
+        let (general_head, general_arguments) = spine(general);
+        let (instance_head, instance_arguments) = spine(instance);
+
+        match general_head {
+            Term::Meta(name) if self.flexible.contains(name) => {
+                match abstract_over(&general_arguments, instance, binders) {
+                    Some(candidate) => match self.theta.get(name) {
+                        Some(existing) => existing.alpha_equivalent(&candidate),
+                        None => {
+                            self.theta.insert(name.clone(), candidate);
+                            true
+                        }
+                    },
+                    None => false,
+                }
+            }
+            Term::Meta(name) => {
+                matches!(instance_head, Term::Meta(other) if other == name) &&
+                    self.match_arguments(&general_arguments, &instance_arguments, binders)
+            }
+            Term::Var(x) => {
+                let general_index = binders.iter().rposition(|(name, _, _)| name == x);
+
+                let heads = match instance_head {
+                    Term::Var(y) => general_index == binders.iter().rposition(|(_, name, _)| name == y) &&
+                        (general_index.is_some() || x == y),
+                    _ => false,
+                };
+
+                heads && self.match_arguments(&general_arguments, &instance_arguments, binders)
+            }
+            _ => general.alpha_equivalent(instance),
+        }
+    }
+
+    fn match_arguments(&mut self, general: &[&Term], instance: &[&Term], binders: &mut Binders) -> bool {
+        general.len() == instance.len() &&
+            general.iter().zip(instance).all(|(general, instance)| self.match_term(general, instance, binders))
+    }
+
+    /// Apply θ to both sides of the constraint.
+    fn apply(&self, constraint: &Constraint) -> Constraint {
+        let mut left = constraint.left.clone();
+        let mut right = constraint.right.clone();
+
+        for (name, with) in &self.theta {
+            let substitution = Substitution::new(name, with.clone());
+            left = meta_substitution(left, substitution.clone());
+            right = meta_substitution(right, substitution);
+        }
+
+        Constraint::new(left, right)
+    }
+}
+
+/// The function `λz1 .. zn. t` that the metavariable applied to the arguments must be for the
+/// application to equal the instance term `t`. The arguments must be distinct bound variables,
+/// and the bound variables of the instance may only occur in `t` if they are among them.
+fn abstract_over(arguments: &[&Term], instance: &Term, binders: &Binders) -> Option<Term> {
+    let mut positions = vec![];
+    for argument in arguments {
+        let position = match argument {
+            Term::Var(x) => binders.iter().rposition(|(name, _, _)| name == x),
+            _ => None,
+        };
+
+        match position {
+            Some(position) if !positions.contains(&position) => positions.push(position),
+            _ => return None,
+        }
+    }
+
+    let free = instance.free_vars();
+    let escapes = binders.iter().enumerate().any(|(index, (_, name, _))| {
+        free.contains(name) && binders.iter().rposition(|(_, other, _)| other == name) == Some(index) && !positions.contains(&index)
+    });
+    if escapes {
+        return None;
+    }
+
+    let fresh_vars = positions.iter().map(|_| generate_fresh_var()).collect::<Vec<_>>();
+
+    let mut builder = instance.clone();
+    for (position, fresh_var) in positions.iter().zip(&fresh_vars) {
+        let substitution = Substitution::new(binders[*position].1.clone(), Term::Var(fresh_var.clone()));
+        builder = term_substitution(builder, substitution);
+    }
+
+    for (position, fresh_var) in positions.iter().zip(&fresh_vars).rev() {
+        builder = Term::Abs(fresh_var.clone(), binders[*position].2.clone(), Box::new(builder));
+    }
+
+    Some(builder)
+}
+
+
+#[test]
+fn test_is_instance_of() {
+    // Arrange
+    let solution = |term: &str| Solution(vec![Substitution::new("I", crate::parse::parse_term(term).unwrap())], vec![]);
+
+    let general = solution("λx:*. result x (?5 x)");
+    let constant = solution("λx:*. result x u32");
+    let repeated = solution("λy:*. result y y");
+    let swapped = solution("λx:*. result u32 x");
+
+    // Act
+    let instances = (constant.is_instance_of(&general), repeated.is_instance_of(&general));
+    let not_instances = (swapped.is_instance_of(&general), general.is_instance_of(&constant));
+
+    // Assert
+    assert_eq!(instances, (true, true));
+    assert_eq!(not_instances, (false, false));
+    assert!(general.is_instance_of(&general));
+}
+
+#[test]
+fn test_is_instance_of_unsubstituted() {
+    // Arrange
+    let unsubstituted = Solution(vec![], vec![]);
+    let substituted = Solution(vec![Substitution::new("I", crate::parse::parse_term("λx:*. option x").unwrap())], vec![]);
+
+    // Act
+    let instance = substituted.is_instance_of(&unsubstituted);
+    let not_instance = unsubstituted.is_instance_of(&substituted);
+
+    // Assert
+    assert!(instance);
+    assert!(!not_instance);
+}
+
+#[test]
+fn test_is_instance_of_respects_residuals() {
+    // Arrange
+    let residual = crate::parse::parse_constraint("?5 u32 =? ?6 u32").unwrap();
+    let general = Solution(vec![Substitution::new("I", crate::parse::parse_term("λx:*. result (?5 x) (?6 x)").unwrap())], vec![residual]);
+    let solved = Solution(vec![Substitution::new("I", crate::parse::parse_term("λx:*. result x x").unwrap())], vec![]);
+    let unsolved = Solution(vec![Substitution::new("I", crate::parse::parse_term("λx:*. result x bool").unwrap())], vec![]);
+
+    // Act
+    let instance = solved.is_instance_of(&general);
+    let not_instance = unsolved.is_instance_of(&general);
+
+    // Assert
+    assert!(instance);
+    assert!(!not_instance);
+}
@@ -0,0 +1,134 @@
+use crate::datatype::{Clash, Constraint, Problem, Substitution};
+use crate::print::PrintMode;
+
+/// What happened at a node of the search tree.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum NodeOutcome {
+    /// The node has not been explored, either yet or because the search stopped early.
+    Unexplored,
+    /// The node exceeded the limits of the search.
+    CutOff,
+    /// A constraint can not be solved, for the given reason.
+    Clash(Clash),
+    /// Only flex-flex constraints remain, so the substitutions on the way down are a solution.
+    /// Holds the index of the solution, in the order the solutions are found.
+    Solved(usize),
+    /// A constraint in the pattern fragment was solved, or a metavariable was pruned, without
+    /// branching.
+    Pattern,
+    /// Imitation and projection were applied to the selected constraint.
+    Matched(Constraint),
+}
+
+/// How the substitution of a node was chosen.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Step {
+    Imitation,
+    Projection,
+    /// The solution of a constraint in the pattern fragment, or a pruning.
+    Pattern,
+}
+
+/// A node of the search tree as recorded by a traced search.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceNode {
+    /// The index of the parent node in the trace, or `None` for the root.
+    pub parent: Option<usize>,
+    /// The indices of the children of the node, in the order they are explored.
+    pub children: Vec<usize>,
+    pub depth: usize,
+    /// The substitution applied to the problem of the parent and how it was chosen, or `None`
+    /// for the root.
+    pub substitution: Option<(Step, Substitution)>,
+    /// The problem of the node, before simplification.
+    pub problem: Problem,
+    /// The problem of the node after simplification, if the node was explored and
+    /// simplification succeeded.
+    pub simplified: Option<Problem>,
+    pub outcome: NodeOutcome,
+}
+
+/// The search tree of a traced search. The nodes are in the order they were created, so the
+/// root comes first and every parent comes before its children.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Trace(pub Vec<TraceNode>);
+
+impl Trace {
+    /// The indices of the children of the node, in the order they are explored.
+    pub fn children(&self, index: usize) -> &[usize] {
+        &self.0[index].children
+    }
+
+    /// The indices of the nodes on the path from the root to the node, including both.
+    pub fn path(&self, index: usize) -> Vec<usize> {
+        let mut path = vec![index];
+        while let Some(parent) = self.0[*path.last().unwrap()].parent {
+            path.push(parent);
+        }
+        path.reverse();
+        path
+    }
+
+    /// The tree in the Graphviz DOT language. The nodes show the simplified problem and the
+    /// outcome, and the edges show the substitutions.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph search {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+        for (index, node) in self.0.iter().enumerate() {
+            let problem = node.simplified.as_ref().unwrap_or(&node.problem);
+            let color = match node.outcome {
+                NodeOutcome::Solved(_) => "green",
+                NodeOutcome::Clash(_) => "red",
+                NodeOutcome::CutOff | NodeOutcome::Unexplored => "gray",
+                NodeOutcome::Pattern | NodeOutcome::Matched(_) => "black",
+            };
+
+            dot.push_str(&format!(
+                "    n{} [label=\"#{}\\n{}\\n{}\", color={}];\n",
+                index, index, escape(&problem.print_in(PrintMode::Plain)), escape(&node.outcome.print_in(PrintMode::Plain)), color
+            ));
+
+            if let (Some(parent), Some((step, substitution))) = (node.parent, &node.substitution) {
+                dot.push_str(&format!(
+                    "    n{} -> n{} [label=\"{}\\n{}\"];\n",
+                    parent, index, step, escape(&substitution.print_in(PrintMode::Plain))
+                ));
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_trace_to_json() {
+    // Arrange
+    let mut unifier = crate::Unifier::default();
+    unifier.declare("u32", crate::Type::Star);
+    unifier.declare("option", crate::parse::parse_type("* -> *").unwrap());
+    let (_, _, trace) = unifier.solve_traced(crate::parse::parse_problem("I u32 =? option u32").unwrap()).unwrap();
+
+    // Act
+    let json = serde_json::to_value(&trace).unwrap();
+
+    // Assert
+    let nodes = json.as_array().unwrap();
+    assert_eq!(nodes.len(), trace.0.len());
+    assert_eq!(nodes[0]["parent"], serde_json::Value::Null);
+    assert!(nodes[0]["outcome"]["matched"].is_object());
+    assert_eq!(nodes[1]["substitution"][0], "projection");
+    assert_eq!(serde_json::from_value::<Trace>(json).unwrap(), trace);
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use crate::datatype::{Constraint, Problem, Substitution, Term, Type};
+
+/// The reason a problem is not well-typed.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TypeError {
+    /// A variable that is neither bound nor declared in the typing context.
+    UnknownConstant(String, Box<Constraint>),
+    /// The two types should be equal, but are not.
+    Mismatch(Type, Type, Box<Constraint>),
+    /// A term would need a type that contains itself, as in `x x`.
+    InfiniteType(Box<Constraint>),
+}
+
+/// A type with unknowns, used during inference.
+#[derive(Clone, PartialEq, Debug)]
+enum Ty {
+    Unknown(usize),
+    Star,
+    Arrow(Box<Ty>, Box<Ty>)
+}
+
+impl Ty {
+    fn from_type(typ: &Type) -> Ty {
+        match typ {
+            Type::Star => Ty::Star,
+            Type::Arrow(t1, t2) => Ty::Arrow(Box::new(Ty::from_type(t1)), Box::new(Ty::from_type(t2)))
+        }
+    }
+}
+
+enum Failure {
+    UnknownConstant(String),
+    Mismatch(Ty, Ty),
+    InfiniteType,
+}
+
+/// Inference of simple types by first-order unification of the unknowns.
+#[derive(Default)]
+struct Inference {
+    solved: HashMap<usize, Ty>,
+    next: usize,
+    metas: HashMap<String, Ty>,
+}
+
+impl Inference {
+    fn fresh(&mut self) -> Ty {
+        self.next += 1;
+        Ty::Unknown(self.next)
+    }
+
+    fn resolve(&self, ty: &Ty) -> Ty {
+        match ty {
+            Ty::Unknown(index) => match self.solved.get(index) {
+                Some(solved) => self.resolve(solved),
+                None => ty.clone(),
+            },
+            Ty::Star => Ty::Star,
+            Ty::Arrow(t1, t2) => Ty::Arrow(Box::new(self.resolve(t1)), Box::new(self.resolve(t2)))
+        }
+    }
+
+    /// The resolved type, where the remaining unknowns default to `*`.
+    fn to_type(&self, ty: &Ty) -> Type {
+        match self.resolve(ty) {
+            Ty::Unknown(_) | Ty::Star => Type::Star,
+            Ty::Arrow(t1, t2) => Type::Arrow(Box::new(self.to_type(&t1)), Box::new(self.to_type(&t2)))
+        }
+    }
+
+    fn occurs(&self, index: usize, ty: &Ty) -> bool {
+        match self.resolve(ty) {
+            Ty::Unknown(other) => other == index,
+            Ty::Star => false,
+            Ty::Arrow(t1, t2) => self.occurs(index, &t1) || self.occurs(index, &t2)
+        }
+    }
+
+    fn unify(&mut self, left: &Ty, right: &Ty) -> Result<(), Failure> {
+        match (self.resolve(left), self.resolve(right)) {
+            (Ty::Unknown(l), Ty::Unknown(r)) if l == r => Ok(()),
+            (Ty::Unknown(index), ty) | (ty, Ty::Unknown(index)) => {
+                if self.occurs(index, &ty) {
+                    return Err(Failure::InfiniteType);
+                }
+                self.solved.insert(index, ty);
+                Ok(())
+            }
+            (Ty::Star, Ty::Star) => Ok(()),
+            (Ty::Arrow(l1, l2), Ty::Arrow(r1, r2)) => {
+                self.unify(&l1, &r1)?;
+                self.unify(&l2, &r2)
+            }
+            (l, r) => Err(Failure::Mismatch(l, r))
+        }
+    }
+
+    fn infer(&mut self, term: &Term, bound: &HashMap<String, Ty>, typing_context: &HashMap<String, Type>) -> Result<Ty, Failure> {
+        match term {
+            Term::Var(s) => match (bound.get(s), typing_context.get(s)) {
+                (Some(ty), _) => Ok(ty.clone()),
+                (None, Some(typ)) => Ok(Ty::from_type(typ)),
+                (None, None) => Err(Failure::UnknownConstant(s.clone())),
+            },
+            Term::Meta(s) => {
+                if let Some(ty) = self.metas.get(s) {
+                    return Ok(ty.clone());
+                }
+
+                let ty = match typing_context.get(s) {
+                    Some(typ) => Ty::from_type(typ),
+                    None => self.fresh(),
+                };
+                self.metas.insert(s.clone(), ty.clone());
+                Ok(ty)
+            }
+            Term::Abs(s, typ, inner) => {
+                let mut new_bound = bound.clone();
+                new_bound.insert(s.clone(), Ty::from_type(typ));
+                let inner = self.infer(inner, &new_bound, typing_context)?;
+                Ok(Ty::Arrow(Box::new(Ty::from_type(typ)), Box::new(inner)))
+            }
+            Term::App(t1, t2) => {
+                let callee = self.infer(t1, bound, typing_context)?;
+                let call_arg = self.infer(t2, bound, typing_context)?;
+                let result = self.fresh();
+                self.unify(&callee, &Ty::Arrow(Box::new(call_arg), Box::new(result.clone())))?;
+                Ok(result)
+            }
+        }
+    }
+
+    fn infer_constraint(&mut self, constraint: &Constraint, typing_context: &HashMap<String, Type>) -> Result<(), TypeError> {
+        let result = self.infer(&constraint.left, &HashMap::new(), typing_context).and_then(|left| {
+            let right = self.infer(&constraint.right, &HashMap::new(), typing_context)?;
+            self.unify(&left, &right)
+        });
+
+        result.map_err(|failure| match failure {
+            Failure::UnknownConstant(s) => TypeError::UnknownConstant(s, Box::new(constraint.clone())),
+            Failure::Mismatch(l, r) => TypeError::Mismatch(self.to_type(&l), self.to_type(&r), Box::new(constraint.clone())),
+            Failure::InfiniteType => TypeError::InfiniteType(Box::new(constraint.clone())),
+        })
+    }
+
+    fn meta_types(&self) -> HashMap<String, Type> {
+        self.metas.iter().map(|(name, ty)| (name.clone(), self.to_type(ty))).collect()
+    }
+}
+
+/// Check that every constraint of the problem relates two terms of the same type, and infer
+/// the types of the metavariables. Metavariables with a type in the typing context keep it,
+/// and the types that are not determined by the problem default to `*`.
+pub fn infer_problem(problem: &Problem, typing_context: &HashMap<String, Type>) -> Result<HashMap<String, Type>, TypeError> {
+    let mut inference = Inference::default();
+
+    for constraint in &problem.0 {
+        inference.infer_constraint(constraint, typing_context)?;
+    }
+
+    Ok(inference.meta_types())
+}
+
+/// Infer the types of the fresh metavariables introduced by a substitution for a metavariable
+/// of known type. Returns no types if the metavariable has no known type or the substitution
+/// is ill-typed.
+pub fn infer_substitution(substitution: &Substitution, typing_context: &HashMap<String, Type>) -> HashMap<String, Type> {
+    let typ = match typing_context.get(&substitution.name) {
+        Some(typ) => typ,
+        None => return HashMap::new(),
+    };
+
+    let mut inference = Inference::default();
+    let result = inference.infer(&substitution.with, &HashMap::new(), typing_context)
+        .and_then(|ty| inference.unify(&ty, &Ty::from_type(typ)));
+
+    match result {
+        Ok(()) => inference.meta_types(),
+        Err(_) => HashMap::new(),
+    }
+}
+
+
+#[test]
+fn test_infer_meta_types() {
+    // Arrange
+    let problem = crate::parse::parse_problem("I u32 =? option u32 ∧ λx:*. P x =? λx:*. result x").unwrap();
+    let typing_context = HashMap::from([
+        ("u32".to_string(), crate::parse::parse_type("*").unwrap()),
+        ("option".to_string(), crate::parse::parse_type("* -> *").unwrap()),
+        ("result".to_string(), crate::parse::parse_type("* -> * -> *").unwrap()),
+    ]);
+
+    // Act
+    let actual = infer_problem(&problem, &typing_context).unwrap();
+
+    // Assert
+    assert_eq!(actual["I"], crate::parse::parse_type("* -> *").unwrap());
+    assert_eq!(actual["P"], crate::parse::parse_type("* -> * -> *").unwrap());
+}
+
+#[test]
+fn test_reject_ill_typed_constraint() {
+    // Arrange
+    let problem = crate::parse::parse_problem("option u32 =? option").unwrap();
+    let typing_context = HashMap::from([
+        ("u32".to_string(), crate::parse::parse_type("*").unwrap()),
+        ("option".to_string(), crate::parse::parse_type("* -> *").unwrap()),
+    ]);
+
+    // Act
+    let actual = infer_problem(&problem, &typing_context);
+
+    // Assert
+    assert!(matches!(actual, Err(TypeError::Mismatch(..))), "{:?}", actual);
+}
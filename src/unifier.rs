@@ -0,0 +1,147 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::datatype::{Context, Problem, Solution, SolutionSet, Type};
+use crate::typing::{infer_problem, TypeError};
+use crate::search::{Search, SearchLimits, SearchOutcome};
+use crate::explain::Explanation;
+use crate::trace::Trace;
+use crate::prioritization::{get_solution_from_solution_set, get_solution_from_solution_set_by_priorities, Priority};
+
+/// The public entry point of the library. A unifier holds the signature (the types of the
+/// constants), the name map used to give nicer binder names to the original metavariables and
+/// the limits of the search. Every search first checks the problem and infers the types of its
+/// metavariables.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Unifier {
+    pub typing_context: HashMap<String, Type>,
+    pub name_map: HashMap<String, Vec<String>>,
+    pub limits: SearchLimits,
+}
+
+impl Unifier {
+    pub fn new(typing_context: HashMap<String, Type>) -> Unifier {
+        Unifier {
+            typing_context,
+            name_map: HashMap::new(),
+            limits: SearchLimits::default(),
+        }
+    }
+
+    pub fn with_name_map(mut self, name_map: HashMap<String, Vec<String>>) -> Unifier {
+        self.name_map = name_map;
+        self
+    }
+
+    pub fn with_limits(mut self, limits: SearchLimits) -> Unifier {
+        self.limits = limits;
+        self
+    }
+
+    /// Declare a constant with the given type in the signature.
+    pub fn declare(&mut self, name: &str, typ: Type) {
+        self.typing_context.insert(name.to_string(), typ);
+    }
+
+    /// Name the binders of the solution for the metavariable `meta`.
+    pub fn name_binders(&mut self, meta: &str, names: &[&str]) {
+        self.name_map.insert(meta.to_string(), names.iter().map(|name| name.to_string()).collect());
+    }
+
+    /// Create a fresh context for a single run of the search.
+    pub fn context(&self) -> Context {
+        Context {
+            typing_context: self.typing_context.clone(),
+            substitutions: vec![],
+            solutions: Rc::new(RefCell::new(vec![])),
+            name_map: self.name_map.clone(),
+        }
+    }
+
+    /// Check the problem and create a search for it, bounded by the limits of the unifier. The
+    /// search runs with the inferred types of the metavariables, such that imitation and
+    /// projection construct correctly typed functions.
+    pub fn search(&self, problem: Problem) -> Result<Search, TypeError> {
+        Ok(self.search_in(self.typed_context(&problem)?, problem))
+    }
+
+    fn search_in(&self, context: Context, problem: Problem) -> Search {
+        Search::new(context, problem).with_limits(self.limits.clone())
+    }
+
+    /// A fresh context whose typing context also holds the inferred types of the metavariables
+    /// of the problem.
+    fn typed_context(&self, problem: &Problem) -> Result<Context, TypeError> {
+        let mut context = self.context();
+        context.typing_context.extend(self.infer(problem)?);
+        Ok(context)
+    }
+
+    /// Check the problem, run the search to the end and collect the solutions in a context.
+    fn run(&self, problem: Problem) -> Result<(Context, Search), TypeError> {
+        let context = self.typed_context(&problem)?;
+        let mut search = self.search_in(context.clone(), problem);
+        context.solutions.borrow_mut().extend(&mut search);
+        Ok((context, search))
+    }
+
+    /// Lazily search for solutions. The solutions are minimized as they are found, so
+    /// the caller can stop after the first one.
+    pub fn solutions(&self, problem: Problem) -> Result<impl Iterator<Item = Solution>, TypeError> {
+        let name_map = self.name_map.clone();
+        Ok(self.search(problem)?.map(move |solution| solution.minimize(&name_map)))
+    }
+
+    /// Run the search and return the minimized solutions without applying the name map.
+    /// This is the form the priority filters expect.
+    pub fn solve_unnamed(&self, problem: Problem) -> Result<SolutionSet, TypeError> {
+        Ok(self.run(problem)?.0.minimal_solutions_without_name_map())
+    }
+
+    /// Run the search and return all the minimized solutions, together with whether the
+    /// limits cut off part of the search. If so, the solutions are the ones found so far.
+    pub fn solve(&self, problem: Problem) -> Result<(SolutionSet, SearchOutcome), TypeError> {
+        let (context, search) = self.run(problem)?;
+        Ok((context.minimal_solutions(), search.outcome()))
+    }
+
+    /// Run the search and return all the minimized solutions, together with the reasons the
+    /// failed branches failed. This explains an empty solution set.
+    pub fn solve_explained(&self, problem: Problem) -> Result<(SolutionSet, Explanation), TypeError> {
+        let (context, search) = self.run(problem)?;
+        Ok((context.minimal_solutions(), search.explanation()))
+    }
+
+    /// Run the search while recording the search tree. The solutions are numbered in the trace
+    /// by their index in the returned set, so duplicates are not removed from it.
+    pub fn solve_traced(&self, problem: Problem) -> Result<(SolutionSet, SearchOutcome, Trace), TypeError> {
+        let mut search = self.search(problem)?.with_trace();
+        let solutions = SolutionSet(search.by_ref().map(|solution| solution.minimize(&self.name_map)).collect());
+        Ok((solutions, search.outcome(), search.trace().cloned().unwrap_or_default()))
+    }
+
+    /// Run the search and filter the solutions by the given priorities, in order.
+    pub fn solve_with_priorities(&self, problem: Problem, priorities: &[Priority]) -> Result<SolutionSet, TypeError> {
+        let filtered = get_solution_from_solution_set_by_priorities(self.solve_unnamed(problem)?, priorities);
+        Ok(self.name(filtered))
+    }
+
+    /// Run the search and filter the solutions by all the priorities. Returns the solution if
+    /// exactly one remains, and otherwise the remaining solutions.
+    pub fn solve_best(&self, problem: Problem) -> Result<Result<Solution, SolutionSet>, TypeError> {
+        Ok(match get_solution_from_solution_set(self.solve_unnamed(problem)?) {
+            Ok(solution) => Ok(solution.name_map(&self.name_map)),
+            Err(solutions) => Err(self.name(solutions)),
+        })
+    }
+
+    /// Check that the problem is well-typed and infer the types of its metavariables.
+    pub fn infer(&self, problem: &Problem) -> Result<HashMap<String, Type>, TypeError> {
+        infer_problem(problem, &self.typing_context)
+    }
+
+    /// Apply the name map to a set of solutions returned by [`Unifier::solve_unnamed`].
+    pub fn name(&self, solutions: SolutionSet) -> SolutionSet {
+        SolutionSet(solutions.0.into_iter().map(|solution| solution.name_map(&self.name_map)).collect())
+    }
+}
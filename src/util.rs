@@ -1,10 +1,10 @@
 
 pub fn amount_of_swaps_to_sort(list: Vec<usize>) -> usize {
     let mut indexed_list = Vec::new();
-    for i in 0..list.len() {
-        indexed_list.push((list[i], i));
+    for (i, element) in list.into_iter().enumerate() {
+        indexed_list.push((element, i));
     }
-    indexed_list.sort_by(|(a, _), (b, _)| a.cmp(b));
+    indexed_list.sort_by_key(|(a, _)| *a);
     let mut marked = vec![false; indexed_list.len()];
     let mut result = 0;
 